@@ -0,0 +1,158 @@
+use std::cmp;
+use headings::HeadingKind;
+use structure::Structure;
+use generate_text::pick_random_in_range;
+
+// How far from its planned position a chapter break may land without being
+//  steered: a tenth of the chapter's planned length, but never less than so
+//  many characters.
+const SLACK_DIVISOR: usize = 10;
+const MIN_SLACK: usize = 50;
+
+// The lengths planned for the chapters of generated text, in characters.
+//  Chapters begin with a heading of the corpus's primary kind, and the
+//  first one is written before any text is generated, so a plan for n
+//  chapters has n - 1 breaks for the generator to make.
+#[derive(Debug,Clone)]
+pub struct ChapterPlan {
+    // The token of the headings which begin chapters, or None if there is
+    //  no plan.
+    pub token: Option<char>,
+    pub lengths: Vec<usize>,
+}
+
+impl ChapterPlan {
+    pub fn none() -> ChapterPlan {
+        ChapterPlan {
+            token: None,
+            lengths: Vec::new(),
+        }
+    }
+
+    // Plan chapters for the given amount of output, in proportions drawn
+    //  from the lengths of the source's chapters, so that the generated book
+    //  is as even (or uneven) as the source. Without a requested number of
+    //  chapters, there are as many as chapters of the source's mean length
+    //  would take.
+
+    pub fn new(kind: HeadingKind,
+               source_lengths: &[usize],
+               output_amount: usize,
+               chapter_count: Option<usize>)
+               -> ChapterPlan {
+        if source_lengths.is_empty() || output_amount == 0 {
+            return ChapterPlan::none();
+        }
+
+        let source_total = source_lengths.iter().sum::<usize>();
+        let mean_length = cmp::max(source_total / source_lengths.len(), 1);
+        let count = match chapter_count {
+            Some(count) => cmp::max(count, 1),
+            None => cmp::max((output_amount + mean_length / 2) / mean_length, 1),
+        };
+
+        let samples = (0..count)
+                          .map(|_| {
+                              source_lengths[pick_random_in_range(0, source_lengths.len() - 1)]
+                          })
+                          .collect::<Vec<_>>();
+        let sample_total = cmp::max(samples.iter().sum::<usize>(), 1);
+        let lengths = samples.iter()
+                             .map(|&sample| cmp::max(sample * output_amount / sample_total, 1))
+                             .collect();
+
+        ChapterPlan {
+            token: Some(Structure::Heading(kind).token()),
+            lengths,
+        }
+    }
+
+    pub fn chapter_count(&self) -> usize {
+        self.lengths.len()
+    }
+
+    // Where the next chapter break should land, once the given number of
+    //  breaks have been made, and how far from there it may land. None once
+    //  every planned break has been made.
+
+    pub fn next_break(&self, breaks_made: usize) -> Option<(usize, usize)> {
+        if breaks_made + 1 >= self.lengths.len() {
+            return None;
+        }
+        let position = self.lengths[..breaks_made + 1]
+                           .iter()
+                           .sum::<usize>();
+        let slack = cmp::max(self.lengths[breaks_made] / SLACK_DIVISOR, MIN_SLACK);
+        Some((position, slack))
+    }
+
+    // A summary of the plan, like "5 chapters of 2000 to 4500 characters".
+
+    pub fn describe(&self) -> String {
+        let shortest = self.lengths.iter().min().cloned().unwrap_or(0);
+        let longest = self.lengths.iter().max().cloned().unwrap_or(0);
+        let plural = if self.lengths.len() == 1 { "" } else { "s" };
+        if shortest == longest {
+            return format!("{} chapter{} of {} characters", self.lengths.len(), plural, shortest);
+        }
+        format!("{} chapter{} of {} to {} characters",
+                self.lengths.len(),
+                plural,
+                shortest,
+                longest)
+    }
+}
+
+// The lengths (in characters, counting structure tokens) of the chapters of
+//  preprocessed text which begin with headings of the given kind. Text
+//  before the first heading (a preface, say) isn't a chapter.
+
+pub fn chapter_lengths(text: &str, kind: HeadingKind) -> Vec<usize> {
+    let token = Structure::Heading(kind).token();
+    let mut lengths = Vec::new();
+    let mut length = None;
+    for c in text.chars() {
+        if c == token {
+            if let Some(length) = length {
+                lengths.push(length);
+            }
+            length = Some(0);
+        } else if let Some(ref mut length) = length {
+            *length += 1;
+        }
+    }
+    if let Some(length) = length {
+        lengths.push(length);
+    }
+    lengths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chapters_are_measured_from_their_headings() {
+        let heading = Structure::Heading(HeadingKind::Chapter).token();
+        let text = format!("Preface{}One{}Three", heading, heading);
+        assert_eq!(chapter_lengths(&text, HeadingKind::Chapter), vec![3, 5]);
+        assert!(chapter_lengths("No headings", HeadingKind::Chapter).is_empty());
+    }
+
+    #[test]
+    fn plans_fill_the_output_amount() {
+        let plan = ChapterPlan::new(HeadingKind::Chapter, &[1000, 3000], 10000, None);
+        assert_eq!(plan.chapter_count(), 5);
+        let total = plan.lengths.iter().sum::<usize>();
+        assert!(total <= 10000 && total > 10000 - plan.chapter_count());
+
+        let plan = ChapterPlan::new(HeadingKind::Chapter, &[1000], 6000, Some(3));
+        assert_eq!(plan.lengths, vec![2000, 2000, 2000]);
+        assert_eq!(plan.describe(), "3 chapters of 2000 characters");
+        assert_eq!(plan.next_break(0), Some((2000, 200)));
+        assert_eq!(plan.next_break(1), Some((4000, 200)));
+        assert_eq!(plan.next_break(2), None);
+
+        assert_eq!(ChapterPlan::new(HeadingKind::Chapter, &[], 6000, None).chapter_count(), 0);
+    }
+}
@@ -0,0 +1,171 @@
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use unicode_normalization::UnicodeNormalization;
+
+// The encodings a text file can be read in. Latin-1 is read as Windows-1252,
+//  which only differs from it in (otherwise unused) control characters.
+#[derive(Debug,Eq,PartialEq,Copy,Clone)]
+pub enum Encoding {
+    Utf8,
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+    Windows1252,
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf8Bom => "UTF-8 with a byte order mark",
+            Encoding::Utf16Le => "UTF-16 (little endian)",
+            Encoding::Utf16Be => "UTF-16 (big endian)",
+            Encoding::Windows1252 => "Latin-1/Windows-1252",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Windows-1252 characters for the bytes 0x80 to 0x9F. The five bytes it
+//  leaves undefined are read as the Latin-1 control characters.
+const WINDOWS_1252_HIGH: [u16; 32] = [0x20AC, 0x0081, 0x201A, 0x0192, 0x201E, 0x2026, 0x2020,
+                                      0x2021, 0x02C6, 0x2030, 0x0160, 0x2039, 0x0152, 0x008D,
+                                      0x017D, 0x008F, 0x0090, 0x2018, 0x2019, 0x201C, 0x201D,
+                                      0x2022, 0x2013, 0x2014, 0x02DC, 0x2122, 0x0161, 0x203A,
+                                      0x0153, 0x009D, 0x017E, 0x0178];
+
+// Read a text file in whichever encoding it is in, with its line endings
+//  normalized to '\n' and its characters to NFC. Returns the text along with
+//  the encoding it was read in.
+
+pub fn read_text_file(file_name: &str) -> Result<(String, Encoding), String> {
+    let mut bytes = Vec::new();
+    File::open(file_name)
+        .and_then(|mut file| file.read_to_end(&mut bytes))
+        .map_err(|error| format!("Could not read {}: {}", file_name, error))?;
+
+    if bytes.is_empty() {
+        return Err(format!("{} is empty.", file_name));
+    }
+
+    let (text, encoding) = decode(&bytes).map_err(|message| {
+        format!("Could not decode {}: {}", file_name, message)
+    })?;
+    if text.chars().any(|c| c == '\0') {
+        return Err(format!("{} does not look like a text file (it contains NUL characters).",
+                           file_name));
+    }
+    if !text.chars().any(|c| c.is_alphabetic()) {
+        return Err(format!("{} contains no text.", file_name));
+    }
+
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    let text = text.nfc().collect::<String>();
+
+    Ok((text, encoding))
+}
+
+pub fn decode(bytes: &[u8]) -> Result<(String, Encoding), String> {
+    if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        return String::from_utf8(bytes[3..].to_vec())
+                   .map(|text| (text, Encoding::Utf8Bom))
+                   .map_err(|error| format!("invalid UTF-8 after a byte order mark ({}).", error));
+    }
+    if bytes.starts_with(&[0xFF, 0xFE]) {
+        return decode_utf16(&bytes[2..], false).map(|text| (text, Encoding::Utf16Le));
+    }
+    if bytes.starts_with(&[0xFE, 0xFF]) {
+        return decode_utf16(&bytes[2..], true).map(|text| (text, Encoding::Utf16Be));
+    }
+
+    // UTF-16 without a byte order mark: mostly ASCII text has a zero in
+    //  every other byte. (Which is valid UTF-8 too, so this goes first.)
+    let even_zeros = bytes.iter().step_by(2).filter(|&&byte| byte == 0).count();
+    let odd_zeros = bytes.iter().skip(1).step_by(2).filter(|&&byte| byte == 0).count();
+    let half = bytes.len() / 2;
+    if odd_zeros * 2 > half && even_zeros * 10 < half {
+        return decode_utf16(bytes, false).map(|text| (text, Encoding::Utf16Le));
+    }
+    if even_zeros * 2 > half && odd_zeros * 10 < half {
+        return decode_utf16(bytes, true).map(|text| (text, Encoding::Utf16Be));
+    }
+
+    if let Ok(text) = String::from_utf8(bytes.to_vec()) {
+        return Ok((text, Encoding::Utf8));
+    }
+
+    Ok((decode_windows_1252(bytes), Encoding::Windows1252))
+}
+
+fn decode_utf16(bytes: &[u8], big_endian: bool) -> Result<String, String> {
+    if !bytes.len().is_multiple_of(2) {
+        return Err(String::from("UTF-16 text with an odd number of bytes."));
+    }
+
+    let units = bytes.chunks(2)
+                     .map(|pair| {
+                         if big_endian {
+                             (pair[0] as u16) << 8 | pair[1] as u16
+                         } else {
+                             (pair[1] as u16) << 8 | pair[0] as u16
+                         }
+                     })
+                     .collect::<Vec<_>>();
+    String::from_utf16(&units).map_err(|_| String::from("invalid UTF-16."))
+}
+
+fn decode_windows_1252(bytes: &[u8]) -> String {
+    bytes.iter()
+         .map(|&byte| {
+             let code = match byte {
+                 0x80..=0x9F => WINDOWS_1252_HIGH[(byte - 0x80) as usize] as u32,
+                 _ => byte as u32,
+             };
+             ::std::char::from_u32(code).unwrap()
+         })
+         .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16(text: &str, big_endian: bool) -> Vec<u8> {
+        text.encode_utf16()
+            .flat_map(|unit| {
+                let (high, low) = ((unit >> 8) as u8, unit as u8);
+                if big_endian {
+                    vec![high, low]
+                } else {
+                    vec![low, high]
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn encodings_are_detected() {
+        let text = "\u{201C}Caf\u{E9}\u{201D}, she said.";
+        assert_eq!(decode(text.as_bytes()), Ok((String::from(text), Encoding::Utf8)));
+
+        let mut with_bom = vec![0xEF, 0xBB, 0xBF];
+        with_bom.extend_from_slice(text.as_bytes());
+        assert_eq!(decode(&with_bom), Ok((String::from(text), Encoding::Utf8Bom)));
+
+        let mut little_endian = vec![0xFF, 0xFE];
+        little_endian.extend(utf16(text, false));
+        assert_eq!(decode(&little_endian), Ok((String::from(text), Encoding::Utf16Le)));
+        assert_eq!(decode(&utf16(text, true)), Ok((String::from(text), Encoding::Utf16Be)));
+
+        let windows_1252 = [0x93, b'C', b'a', b'f', 0xE9, 0x94, b',', b' ', b's', b'h', b'e', b' ',
+                            b's', b'a', b'i', b'd', b'.'];
+        assert_eq!(decode(&windows_1252), Ok((String::from(text), Encoding::Windows1252)));
+    }
+
+    #[test]
+    fn broken_utf16_is_an_error() {
+        assert!(decode(&[0xFF, 0xFE, b'a']).is_err());
+        assert!(decode(&[0xFF, 0xFE, 0x00, 0xD8, b'a', 0x00]).is_err());
+    }
+}
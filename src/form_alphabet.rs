@@ -0,0 +1,298 @@
+// The symbols that words are abstracted to when extracting the form of a
+//  text. By default every word is an 'x', and runs of words separated by
+//  single spaces collapse into one 'x' (unless words are kept separate). Word
+//  length buckets, capitalization classes and numerals can be distinguished
+//  as well, in which case each word becomes one symbol (and the single spaces
+//  between words are implied):
+//
+//   - lowercase words:   'a', 'b', 'c', ... (one per length bucket)
+//   - Capitalized words: 'A', 'B', 'C', ...
+//   - UPPERCASE words:   'U', 'V', 'W', ...
+//   - numerals:          'n'
+
+const WORD: char = 'x';
+const NUMERAL: char = 'n';
+const MAX_LENGTH_BOUNDS: usize = 5;
+
+#[derive(Debug,Clone)]
+pub struct FormAlphabet {
+    // Inclusive upper bounds of the word lengths in each bucket. Words longer
+    //  than the last bound go in a final bucket of their own.
+    pub length_bounds: Vec<usize>,
+    pub capitalization: bool,
+    pub numerals: bool,
+    pub separate_words: bool,
+}
+
+impl FormAlphabet {
+    pub fn new() -> FormAlphabet {
+        FormAlphabet {
+            length_bounds: Vec::new(),
+            capitalization: false,
+            numerals: false,
+            separate_words: false,
+        }
+    }
+
+    // Parse a comma separated list of word length bounds and options, such as
+    //  "3,7,caps,num" ("words" keeps runs of words from collapsing into one).
+
+    pub fn parse(spec: &str) -> Result<FormAlphabet, String> {
+        let mut alphabet = FormAlphabet::new();
+
+        for piece in spec.split(',').map(|piece| piece.trim()).filter(|piece| !piece.is_empty()) {
+            match piece {
+                "caps" => alphabet.capitalization = true,
+                "num" => alphabet.numerals = true,
+                "words" => alphabet.separate_words = true,
+                _ => {
+                    let bound = piece.parse::<usize>().map_err(|_| {
+                        format!("Unknown form alphabet option: '{}'.", piece)
+                    })?;
+                    if let Some(&last) = alphabet.length_bounds.last() {
+                        if bound <= last {
+                            return Err(String::from("Word length bounds must be increasing."));
+                        }
+                    }
+                    alphabet.length_bounds.push(bound);
+                }
+            }
+        }
+
+        if alphabet.length_bounds.len() > MAX_LENGTH_BOUNDS {
+            return Err(format!("At most {} word length bounds are supported.",
+                               MAX_LENGTH_BOUNDS));
+        }
+
+        Ok(alphabet)
+    }
+
+    pub fn is_word_char(&self, c: char) -> bool {
+        c.is_alphabetic() || c == '-' || (self.numerals && c.is_numeric())
+    }
+
+    pub fn classify(&self, word: &str) -> char {
+        if self.numerals && word.chars().any(|c| c.is_numeric()) {
+            return NUMERAL;
+        }
+        if self.length_bounds.is_empty() && !self.capitalization {
+            return WORD;
+        }
+
+        let length = word.chars().filter(|c| c.is_alphabetic()).count();
+        let bucket = self.length_bounds.iter().take_while(|&&bound| length > bound).count() as u8;
+
+        let mut letters = word.chars().filter(|c| c.is_alphabetic());
+        let first = letters.next();
+        let capitalized = self.capitalization && first.is_some_and(|c| c.is_uppercase());
+        let uppercase = capitalized && length > 1 && letters.all(|c| c.is_uppercase());
+
+        let base = if uppercase {
+            b'U'
+        } else if capitalized {
+            b'A'
+        } else {
+            b'a'
+        };
+
+        (base + bucket) as char
+    }
+
+    // Whether a word which has been read up to this point could still end up
+    //  as the given symbol.
+
+    pub fn could_become(&self, word: &str, symbol: char) -> bool {
+        if symbol == NUMERAL && self.numerals {
+            return true;
+        }
+        if self.numerals && word.chars().any(|c| c.is_numeric()) {
+            return false;
+        }
+        if self.length_bounds.is_empty() && !self.capitalization {
+            return symbol == WORD;
+        }
+
+        let (base, uppercase_class) = match symbol {
+            'U'..='Z' if self.capitalization => (b'U', true),
+            'A'..='F' if self.capitalization => (b'A', false),
+            'a'..='f' => (b'a', false),
+            _ => return false,
+        };
+        let bucket = (symbol as u8 - base) as usize;
+        if bucket > self.length_bounds.len() {
+            return false;
+        }
+
+        let length = word.chars().filter(|c| c.is_alphabetic()).count();
+        if bucket < self.length_bounds.len() && length > self.length_bounds[bucket] {
+            return false;
+        }
+
+        let mut letters = word.chars().filter(|c| c.is_alphabetic());
+        match letters.next() {
+            None => true,
+            Some(first) if !self.capitalization || !first.is_uppercase() => base == b'a',
+            Some(_) if uppercase_class => letters.all(|c| c.is_uppercase()),
+            Some(_) => base == b'A',
+        }
+    }
+
+    fn merges(&self, symbol: char) -> bool {
+        symbol == WORD && !self.separate_words
+    }
+}
+
+// Turns text into form one character at a time. The symbol for a word is only
+//  known once the word has ended, so it is emitted then.
+
+#[derive(Debug,Clone)]
+pub struct FormTokenizer<'a> {
+    alphabet: &'a FormAlphabet,
+    word: String,
+    last_symbol: Option<char>,
+    pending_space: bool,
+}
+
+impl<'a> FormTokenizer<'a> {
+    pub fn new(alphabet: &'a FormAlphabet) -> FormTokenizer<'a> {
+        FormTokenizer {
+            alphabet,
+            word: String::new(),
+            last_symbol: None,
+            pending_space: false,
+        }
+    }
+
+    pub fn sync(&mut self, target: &FormTokenizer) {
+        self.word.clear();
+        self.word.push_str(&target.word);
+
+        self.last_symbol = target.last_symbol;
+        self.pending_space = target.pending_space;
+    }
+
+    pub fn is_reading_word(&self) -> bool {
+        !self.word.is_empty()
+    }
+
+    pub fn word_length(&self) -> usize {
+        self.word.chars().count()
+    }
+
+    pub fn has_pending_space(&self) -> bool {
+        self.pending_space
+    }
+
+    pub fn word_could_become(&self, symbol: char) -> bool {
+        self.alphabet.could_become(&self.word, symbol)
+    }
+
+    // Read the next character of text, adding to the form as necessary.
+    //  Returns whether the form changed, and whether the character began a
+    //  new sequence of words (one that follows punctuation rather than a
+    //  single space).
+
+    pub fn push(&mut self, next_char: char, form: &mut String) -> (bool, bool) {
+        if self.alphabet.is_word_char(next_char) {
+            let begins_sequence = self.word.is_empty() && !self.pending_space;
+            self.word.push(next_char);
+            return (false, begins_sequence);
+        }
+
+        let mut changed = self.end_word(form);
+
+        if next_char == ' ' && self.last_symbol.is_some() && !self.pending_space {
+            // Might be the space between two words, which is implied.
+            self.pending_space = true;
+            return (changed, false);
+        }
+
+        if self.pending_space {
+            form.push(' ');
+            self.pending_space = false;
+        }
+        form.push(next_char);
+        self.last_symbol = None;
+        changed = true;
+
+        (changed, false)
+    }
+
+    // Emit the symbol for any word still being read.
+
+    pub fn finish(&mut self, form: &mut String) {
+        self.end_word(form);
+        if self.pending_space {
+            form.push(' ');
+            self.pending_space = false;
+        }
+    }
+
+    fn end_word(&mut self, form: &mut String) -> bool {
+        if self.word.is_empty() {
+            return false;
+        }
+
+        let symbol = self.alphabet.classify(&self.word);
+        self.word.clear();
+
+        let merged = self.pending_space && self.last_symbol == Some(symbol) &&
+                     self.alphabet.merges(symbol);
+        self.pending_space = false;
+        self.last_symbol = Some(symbol);
+
+        if merged {
+            return false;
+        }
+        form.push(symbol);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn form(text: &str, alphabet: &FormAlphabet) -> String {
+        let mut tokenizer = FormTokenizer::new(alphabet);
+        let mut form = String::new();
+        for c in text.chars() {
+            tokenizer.push(c, &mut form);
+        }
+        tokenizer.finish(&mut form);
+        form
+    }
+
+    #[test]
+    fn options_and_bounds_parse() {
+        let alphabet = FormAlphabet::parse("3, 7,caps,num").unwrap();
+        assert_eq!(alphabet.length_bounds, vec![3, 7]);
+        assert!(alphabet.capitalization && alphabet.numerals && !alphabet.separate_words);
+
+        assert!(FormAlphabet::parse("7,3").is_err());
+        assert!(FormAlphabet::parse("3,loud").is_err());
+        assert!(FormAlphabet::parse("1,2,3,4,5,6").is_err());
+    }
+
+    #[test]
+    fn words_are_classified_by_length_and_case() {
+        let alphabet = FormAlphabet::parse("3,7,caps,num").unwrap();
+        assert_eq!(alphabet.classify("cat"), 'a');
+        assert_eq!(alphabet.classify("rabbit"), 'b');
+        assert_eq!(alphabet.classify("marmalade"), 'c');
+        assert_eq!(alphabet.classify("Alice"), 'B');
+        assert_eq!(alphabet.classify("ALICE"), 'V');
+        assert_eq!(alphabet.classify("1865"), 'n');
+
+        assert!(alphabet.could_become("Ali", 'B'));
+        assert!(!alphabet.could_become("Alice", 'A'));
+        assert!(!alphabet.could_become("ali", 'B'));
+    }
+
+    #[test]
+    fn runs_of_words_collapse_unless_kept_separate() {
+        assert_eq!(form("One two, three.", &FormAlphabet::new()), "x, x.");
+        assert_eq!(form("One two, three.", &FormAlphabet::parse("words").unwrap()), "xx, x.");
+        assert_eq!(form("One two, three.", &FormAlphabet::parse("3,caps").unwrap()), "Aa, b.");
+    }
+}
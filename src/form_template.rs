@@ -0,0 +1,67 @@
+use form_alphabet::{FormAlphabet, FormTokenizer};
+use preprocess::extract_form;
+
+// Words longer than this don't fill a slot; it's more likely that the word
+//  couldn't be ended with what the template needs next.
+const MAX_WORD_LENGTH: usize = 14;
+
+// The exact form of a passage (such as a poem or a letter), for generating
+//  new text with the same punctuation, line breaks and word count. Words
+//  should be kept separate in the alphabet, so that each word is a slot.
+
+pub struct FormTemplate {
+    form: Vec<char>,
+}
+
+impl FormTemplate {
+    pub fn new(processed_text: &str, alphabet: &FormAlphabet) -> FormTemplate {
+        FormTemplate { form: extract_form(processed_text, alphabet).chars().collect() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.form.len()
+    }
+
+    // Whether a tokenizer is still on track to produce the template, given
+    //  that it had produced `position` symbols before most recently adding
+    //  `added`.
+
+    pub fn allows(&self, position: usize, added: &str, tokenizer: &FormTokenizer) -> bool {
+        let mut position = position;
+        for symbol in added.chars() {
+            if position >= self.form.len() || self.form[position] != symbol {
+                return false;
+            }
+            position += 1;
+        }
+
+        if tokenizer.is_reading_word() {
+            return position < self.form.len() && tokenizer.word_length() <= MAX_WORD_LENGTH &&
+                   tokenizer.word_could_become(self.form[position]);
+        }
+        if tokenizer.has_pending_space() {
+            return position == self.form.len() || self.form[position] == ' ' ||
+                   self.form[position].is_alphabetic();
+        }
+        true
+    }
+
+    // Whether the template could begin with the given text.
+
+    pub fn fits_start(&self, text: &str, alphabet: &FormAlphabet) -> bool {
+        let mut tokenizer = FormTokenizer::new(alphabet);
+        let mut position = 0;
+        let mut added = String::new();
+
+        for c in text.chars() {
+            added.clear();
+            tokenizer.push(c, &mut added);
+            if !self.allows(position, &added, &tokenizer) {
+                return false;
+            }
+            position += added.chars().count();
+        }
+
+        true
+    }
+}
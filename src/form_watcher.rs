@@ -1,6 +1,6 @@
-
-use std::cmp;
 use gather_stats::OrderStats;
+use form_alphabet::{FormAlphabet, FormTokenizer};
+use form_template::FormTemplate;
 
 pub struct FormWatcher<'a> {
     // immutables:
@@ -8,19 +8,24 @@ pub struct FormWatcher<'a> {
     max_order: usize,
     current: String,
     pub current_order: usize,
-    saw_possible_form_space: bool,
-    is_within_form_sequence: bool,
+    tokenizer: FormTokenizer<'a>,
+    added: String,
+    form_length: usize,
 }
 
 impl<'a> FormWatcher<'a> {
-    pub fn new(stats: &'a Vec<OrderStats<'a>>, max_order: usize) -> FormWatcher<'a> {
+    pub fn new(stats: &'a Vec<OrderStats<'a>>,
+               max_order: usize,
+               alphabet: &'a FormAlphabet)
+               -> FormWatcher<'a> {
         FormWatcher {
-            stats: stats,
-            max_order: max_order,
+            stats,
+            max_order,
             current: String::new(),
             current_order: 0,
-            saw_possible_form_space: false,
-            is_within_form_sequence: false,
+            tokenizer: FormTokenizer::new(alphabet),
+            added: String::new(),
+            form_length: 0,
         }
     }
 
@@ -29,59 +34,85 @@ impl<'a> FormWatcher<'a> {
         self.current.push_str(&target.current);
 
         self.current_order = target.current_order;
-        self.saw_possible_form_space = target.saw_possible_form_space;
-        self.is_within_form_sequence = target.is_within_form_sequence;
+        self.tokenizer.sync(&target.tokenizer);
+        self.form_length = target.form_length;
+    }
+
+    // The number of form symbols seen so far.
+
+    pub fn form_length(&self) -> usize {
+        self.form_length
+    }
+
+    // Whether the next character would keep the form on track to match the
+    //  template.
+
+    pub fn fits(&self, template: &FormTemplate, next_char: char) -> bool {
+        let mut tokenizer = self.tokenizer.clone();
+        let mut added = String::new();
+        tokenizer.push(next_char, &mut added);
+        template.allows(self.form_length, &added, &tokenizer)
     }
 
     pub fn watch(&mut self, next_char: char) -> (usize, bool) {
 
         // Add form entries to output if necessary:
-        let mut changed = false;
-        let last = self.current.chars().next_back();
-        if next_char.is_alphabetic() || next_char == '-' {
-            if last.is_none() || last.unwrap() != 'x' {
-                self.current.push('x');
-                changed = true;
-            }
-            self.saw_possible_form_space = false;
-            self.is_within_form_sequence = false;
-        } else {
-            if next_char == ' ' && last.is_some() && last.unwrap() == 'x' {
-                self.saw_possible_form_space = true;
-            } else {
-                if self.saw_possible_form_space {
-                    self.current.push(' ');
-                    self.saw_possible_form_space = false;
-                }
-                self.current.push(next_char);
-                self.is_within_form_sequence = true;
-                changed = true;
-            }
-        }
+        self.added.clear();
+        let (changed, begins_sequence) = self.tokenizer.push(next_char, &mut self.added);
+        self.current.push_str(&self.added);
+        self.form_length += self.added.chars().count();
 
         let n = self.current.chars().count();
         let max = self.max_order;
-        let remove_count = if n > max {
-            n - max
-        } else {
-            0
-        };
+        let remove_count = n.saturating_sub(max);
         for _ in 0..remove_count {
             self.current.remove(0);
         }
 
-        let mut ord = self.current.chars().count() - 1;
         if changed {
-            while !self.stats[ord].stats_for_state.contains_key(&self.current[..]) {
+            // Back off to the longest form sequence that has been seen before.
+            while !self.current.is_empty() &&
+                  !self.stats[self.current.chars().count() - 1]
+                       .stats_for_state
+                       .contains_key(&self.current[..]) {
                 self.current.remove(0);
-                ord -= 1;
             }
+            self.current_order = if !self.current.is_empty() {
+                self.current.chars().count() - 1
+            } else {
+                0
+            };
         }
 
-        self.current_order = ord;
+        (self.current_order, begins_sequence)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gather_stats::gather_stats;
+    use preprocess::extract_form;
+
+    fn coherence(text: &str, stats: &Vec<OrderStats>, alphabet: &FormAlphabet) -> usize {
+        let mut watcher = FormWatcher::new(stats, 8, alphabet);
+        let mut order = 0;
+        for c in text.chars() {
+            order = watcher.watch(c).0;
+        }
+        order
+    }
 
-        let report_change = changed && !self.is_within_form_sequence;
+    #[test]
+    fn form_is_judged_by_the_corpus_it_was_learned_from() {
+        let alphabet = FormAlphabet::new();
+        let exclamations = extract_form(&"Oh no! Ah well! ".repeat(10), &alphabet);
+        let clauses = extract_form(&"The cat sat; the dog ran, the end. ".repeat(10), &alphabet);
+        let exclamation_stats = gather_stats(&exclamations, 8);
+        let clause_stats = gather_stats(&clauses, 8);
 
-        return (ord, report_change);
+        let text = "Up we go! Down it came! Off";
+        assert!(coherence(text, &exclamation_stats, &alphabet) >
+                coherence(text, &clause_stats, &alphabet));
     }
 }
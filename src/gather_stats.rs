@@ -1,5 +1,5 @@
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::collections::VecDeque;
 
 #[derive(Debug)]
@@ -17,7 +17,7 @@ impl<'a> OrderStats<'a> {
             .or_insert_with(|| {
                 CharChoiceStats {
                     total_usages: 0,
-                    options: HashMap::new(),
+                    options: BTreeMap::new(),
                 }
             })
             .add_option(next);
@@ -27,7 +27,7 @@ impl<'a> OrderStats<'a> {
 #[derive(Debug)]
 pub struct CharChoiceStats {
     pub total_usages: i32,
-    pub options: HashMap<char, i32>,
+    pub options: BTreeMap<char, i32>,
 }
 
 impl CharChoiceStats {
@@ -45,7 +45,7 @@ impl CharChoiceStats {
 //  a given character after a configurable (MAX_ORDER) number of
 //  characters has been encountered.
 
-pub fn gather_stats(text: &str, max_order: usize) -> Vec<OrderStats> {
+pub fn gather_stats(text: &str, max_order: usize) -> Vec<OrderStats<'_>> {
     let mut stats: Vec<OrderStats> = Vec::new();
     for _ in 0..max_order {
         let order_stats = OrderStats {
@@ -86,5 +86,5 @@ pub fn gather_stats(text: &str, max_order: usize) -> Vec<OrderStats> {
         }
     }
 
-    return stats;
+    stats
 }
@@ -1,22 +1,75 @@
 
 use std::cmp;
-use std::collections::HashMap;
-use rand;
-use rand::random;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use rand::{Rng, SeedableRng, XorShiftRng};
 use num;
 use num::traits::NumCast;
 use gather_stats::{OrderStats, CharChoiceStats};
+use form_alphabet::FormAlphabet;
+use structure::{Structure, Token};
+use sentence::SentenceWatcher;
+use writer::Format;
+use chapter_plan::ChapterPlan;
 
-#[derive(Debug)]
+#[derive(Debug,Clone)]
 pub struct Args {
     pub input_filename: String,
+    pub form_input_filename: Option<String>,
     pub output_filename: String,
     pub lower_order_bound: usize,
     pub higher_order_bound: usize,
     pub max_tries: usize,
+    pub backtrack_depth: usize,
+    pub backtrack_budget: usize,
     pub distortion_factor: i32,
+    // The mean number of words in a sentence of the input, which sentence
+    //  lengths are distorted toward (measured rather than given).
+    pub sentence_length: usize,
+    // The number of chapters to generate, if given.
+    pub chapter_count: Option<usize>,
+    // The number of books to generate in series mode.
+    pub volume_count: usize,
+    // The planned lengths of chapters, which breaks are steered toward
+    //  (measured from the input).
+    pub chapter_plan: ChapterPlan,
     pub output_amount: usize,
-    pub use_html: bool,
+    pub format: Format,
+    pub reflow_width: usize,
+    pub seed: u32,
+    pub threads: usize,
+    pub form_alphabet: FormAlphabet,
+    pub template_filename: Option<String>,
+    pub heading_filename: Option<String>,
+}
+
+// Arguments for tests: orders 1 to 4, a single try and a fixed seed, without
+//  distortion.
+#[cfg(test)]
+pub fn test_args() -> Args {
+    Args {
+        input_filename: String::from("test.txt"),
+        form_input_filename: None,
+        output_filename: String::from("output.txt"),
+        lower_order_bound: 1,
+        higher_order_bound: 4,
+        max_tries: 1,
+        backtrack_depth: 0,
+        backtrack_budget: 0,
+        distortion_factor: 0,
+        sentence_length: 0,
+        chapter_count: None,
+        volume_count: 1,
+        chapter_plan: ChapterPlan::none(),
+        output_amount: 100,
+        format: Format::Text,
+        reflow_width: 0,
+        seed: 1,
+        threads: 1,
+        form_alphabet: FormAlphabet::new(),
+        template_filename: None,
+        heading_filename: None,
+    }
 }
 
 pub struct Generator<'a> {
@@ -24,9 +77,9 @@ pub struct Generator<'a> {
     stats: &'a Vec<OrderStats<'a>>,
     max_order: usize,
     min_order: usize,
-    output_amount: usize,
-    use_html: bool,
     distortion_factor: i32,
+    sentence_length: usize,
+    chapter_plan: ChapterPlan,
 
     // current state:
     //
@@ -36,6 +89,10 @@ pub struct Generator<'a> {
     total: usize,
     change_order_counter: i32,
     distortions: CharChoiceStats,
+    sentences: SentenceWatcher,
+    // The number of chapter breaks made so far.
+    chapter_breaks: usize,
+    rng: XorShiftRng,
 }
 
 impl<'a> Generator<'a> {
@@ -44,14 +101,13 @@ impl<'a> Generator<'a> {
                min_order: usize,
                max_order: usize)
                -> Generator<'a> {
-
-        let generator = Generator {
-            stats: stats,
-            max_order: max_order,
-            min_order: min_order,
-            output_amount: args.output_amount,
-            use_html: args.use_html,
+        Generator {
+            stats,
+            max_order,
+            min_order,
             distortion_factor: args.distortion_factor,
+            sentence_length: args.sentence_length,
+            chapter_plan: args.chapter_plan.clone(),
 
             current: String::new(),
             current_order: max_order,
@@ -59,11 +115,20 @@ impl<'a> Generator<'a> {
             change_order_counter: 0,
             distortions: CharChoiceStats {
                 total_usages: 0,
-                options: HashMap::new(),
+                options: BTreeMap::new(),
             },
-        };
+            sentences: SentenceWatcher::new(),
+            chapter_breaks: 0,
+            rng: create_rng(args.seed, 0),
+        }
+    }
 
-        return generator;
+    // Switch to a different stream of random numbers derived from the seed, so
+    //  that several generators working from the same state make different
+    //  choices.
+
+    pub fn set_stream(&mut self, seed: u32, stream: u32) {
+        self.rng = create_rng(seed, stream);
     }
 
     pub fn sync(&mut self, target: &Generator) {
@@ -74,20 +139,27 @@ impl<'a> Generator<'a> {
         self.current_order = target.current_order;
         self.total = target.total;
         self.change_order_counter = target.change_order_counter;
+        self.sentences.sync(&target.sentences);
+        self.chapter_breaks = target.chapter_breaks;
     }
 
     // Choose random starting string (encountered in the input text)
     //  of length MAX_ORDER.
 
     pub fn start(&mut self, start: Option<&str>) {
-        if start.is_some() {
-            self.current = String::from(start.unwrap());
+        self.sentences = SentenceWatcher::new();
+        if let Some(start) = start {
+            self.current = String::from(start);
+            for c in self.current.chars() {
+                self.sentences.push(c);
+            }
         } else {
-            let start_index = pick_random_in_range(0,
-                                                   self.stats[self.current_order - 1]
-                                                       .stats_for_state
-                                                       .len() -
-                                                   1);
+            let start_index = pick_random_in_range_with(&mut self.rng,
+                                                        0,
+                                                        self.stats[self.current_order - 1]
+                                                            .stats_for_state
+                                                            .len() -
+                                                        1);
             self.current = String::from(*self.stats[self.current_order - 1]
                                              .stats_for_state
                                              .keys()
@@ -98,7 +170,7 @@ impl<'a> Generator<'a> {
 
     fn update_order_used(&mut self) {
         if self.change_order_counter == 0 {
-            if pick_random_in_range(0, 1) == 0 {
+            if pick_random_in_range_with(&mut self.rng, 0, 1) == 0 {
                 if self.current_order > self.min_order {
                     self.current_order -= 1;
                 }
@@ -116,36 +188,163 @@ impl<'a> Generator<'a> {
     fn calculate_distortions(&mut self, choice_stats: &CharChoiceStats) {
         self.distortions.total_usages = choice_stats.total_usages;
         self.distortions.options.clear();
+        if self.distortion_factor > 1 {
+            self.distort_sentence_lengths(choice_stats);
+        }
+        // Chapter breaks are steered whatever the distortion factor, as the
+        //  search waits for them before it stops.
+        self.distort_chapter_lengths(choice_stats);
+    }
+
+    // Sentences are steered toward the length of the input's: while the
+    //  current sentence is much shorter, anything that would end it is made
+    //  less likely, and once it is much longer, more likely.
+
+    fn distort_sentence_lengths(&mut self, choice_stats: &CharChoiceStats) {
+        if self.sentence_length == 0 {
+            return;
+        }
+
+        let words = self.sentences.word_count();
         for (char_choice, count) in choice_stats.options.iter() {
-            // if self.sentence_watcher.enders.contains(char_choice) {
-            // 	let new_count = if self.current_sentence_length > self.sentence_watcher.word_count {
-            // 		(*count as f64 / self.distortion_factor as f64).ceil() as i32
-            // 	} else {
-            // 		count * self.distortion_factor
-            // 	};
-            // 	self.distortions.total_usages += new_count - count;
-            // 	self.distortions.options.insert(*char_choice, new_count);
-            // }
+            if !self.sentences.would_end(*char_choice) {
+                continue;
+            }
+            let new_count = if words * 2 < self.sentence_length {
+                (*count as f64 / self.distortion_factor as f64).ceil() as i32
+            } else if words * 2 > self.sentence_length * 3 {
+                count * self.distortion_factor
+            } else {
+                continue;
+            };
+            self.distortions.total_usages += new_count - count;
+            self.distortions.options.insert(*char_choice, new_count);
+        }
+    }
+
+    // Chapter breaks are steered toward the chapter plan: a break is ruled
+    //  out until the next planned one is near, and then made likely wherever
+    //  one could go (where the input had one, or at the end of a paragraph),
+    //  more so the further past the plan the text runs. Once every planned
+    //  break has been made, no more are.
+
+    fn distort_chapter_lengths(&mut self, choice_stats: &CharChoiceStats) {
+        let token = match self.chapter_plan.token {
+            Some(token) => token,
+            None => return,
+        };
+        let count = choice_stats.options.get(&token).cloned().unwrap_or(0);
+        // Nothing changes where no break could go, or where a break is the
+        //  only way on.
+        if !self.could_break(choice_stats) || count == choice_stats.total_usages {
+            return;
+        }
+
+        let new_count = match self.chapter_plan.next_break(self.chapter_breaks) {
+            _ if !self.chapter_break_allowed() => 0,
+            Some((position, slack)) => {
+                // As likely as everything else together at first, and up to
+                //  ten times as likely, a slack's worth of overrun at a time.
+                let overrun = cmp::min(self.total.saturating_sub(position) / slack, 9) as i32;
+                cmp::max(count * self.distortion_factor,
+                         choice_stats.total_usages * (overrun + 1))
+            }
+            None => 0,
+        };
+        self.distortions.total_usages += new_count - count;
+        self.distortions.options.insert(token, new_count);
+    }
+
+    // Whether a chapter could begin next: at the end of a paragraph or
+    //  stanza, or where the input has a heading of any kind.
+
+    fn could_break(&self, choice_stats: &CharChoiceStats) -> bool {
+        let last = self.current.chars().next_back();
+        let after_paragraph = matches!(last.and_then(Structure::from_token),
+                                       Some(Structure::Paragraph) | Some(Structure::Stanza));
+        let heading_possible = choice_stats.options
+                                           .keys()
+                                           .any(|&c| {
+                                               matches!(Structure::from_token(c),
+                                                        Some(Structure::Heading(_)))
+                                           });
+        after_paragraph || heading_possible
+    }
+
+    // Whether a chapter break may be made now, according to the plan.
+
+    fn chapter_break_allowed(&self) -> bool {
+        match self.chapter_plan.next_break(self.chapter_breaks) {
+            Some((position, slack)) => self.total + slack >= position,
+            None => self.chapter_plan.token.is_none(),
         }
     }
 
-    pub fn next(&mut self) -> (char, usize) {
+    // Whether the text generated so far ends at the end of a sentence.
+
+    pub fn ends_sentence(&self) -> bool {
+        self.sentences.ends_sentence()
+    }
+
+    pub fn sentence_count(&self) -> usize {
+        self.sentences.sentence_count()
+    }
+
+    // The number of chapter breaks made so far.
+
+    pub fn chapter_breaks(&self) -> usize {
+        self.chapter_breaks
+    }
+
+    // The number of planned chapter breaks still to be made.
+
+    pub fn chapter_breaks_remaining(&self) -> usize {
+        let planned = self.chapter_plan.chapter_count().saturating_sub(1);
+        planned.saturating_sub(self.chapter_breaks)
+    }
+
+    // Choose the next character, which is emitted as a token: text, or a
+    //  piece of structure.
+
+    pub fn next(&mut self) -> (Token, usize) {
         let order_used = self.current_order;
-        let choice_stats = if let Some(choice_stats) = self.stats[self.current_order - 1]
-                                                           .stats_for_state
-                                                           .get(&self.current[..]) {
-            choice_stats
+        let stats = self.stats;
+        let found = stats[self.current_order - 1].stats_for_state.get(&self.current[..]);
+
+        // The state may be new if an earlier choice was made with a shorter
+        //  key, or its only choice may be a chapter break which isn't due
+        //  yet. Either way, back off to a shorter key.
+        let ruled_out = if self.chapter_break_allowed() {
+            None
         } else {
-            panic!("Current state could not be found in stats. Key: '{}' (length: {}), Order: {}",
-                   self.current,
-                   self.current.chars().count(),
-                   self.current_order);
+            self.chapter_plan.token
+        };
+        let only_ruled_out = found.is_some_and(|choice_stats| {
+            choice_stats.options.len() == 1 &&
+            ruled_out.is_some_and(|token| choice_stats.options.contains_key(&token))
+        });
+        if found.is_none() || only_ruled_out {
+            if let Some(next_item) = self.next_where(|c| Some(c) != ruled_out) {
+                return next_item;
+            }
+        }
+        let choice_stats = match found {
+            Some(choice_stats) => choice_stats,
+            None => {
+                panic!("Current state could not be found in stats. Key: '{}' (length: {}), \
+                        Order: {}",
+                       self.current,
+                       self.current.chars().count(),
+                       self.current_order)
+            }
         };
 
         self.update_order_used();
-        self.calculate_distortions(&choice_stats);
+        self.calculate_distortions(choice_stats);
 
-        let mut choice_num = pick_random_in_range(1, self.distortions.total_usages);
+        let mut choice_num = pick_random_in_range_with(&mut self.rng,
+                                                       1,
+                                                       self.distortions.total_usages);
 
         for (next_char, next_count) in choice_stats.options.iter() {
             let mut count = *next_count;
@@ -155,19 +354,110 @@ impl<'a> Generator<'a> {
             choice_num -= count;
 
             if choice_num <= 0 {
-                self.current.push(*next_char);
-                self.total += 1;
+                self.advance(*next_char);
+                return (Token::from_char(*next_char), order_used);
+            }
+        }
+        // Distortions can add choices the input never made here (a chapter
+        //  break, say).
+        for (next_char, count) in self.distortions.options.iter() {
+            if choice_stats.options.contains_key(next_char) {
+                continue;
+            }
+            choice_num -= *count;
 
-                let remove_count = cmp::max(self.current.chars().count() - self.current_order, 0);
-                for _ in 0..remove_count {
-                    self.current.remove(0);
+            if choice_num <= 0 {
+                let next_char = *next_char;
+                self.advance(next_char);
+                return (Token::from_char(next_char), order_used);
+            }
+        }
+
+        panic!("Failed to choose a next character.");
+    }
+
+    // Like next, but only chooses among characters that are allowed. When
+    //  none of the options for the current key are allowed, shorter keys are
+    //  tried, down to a single character, and then any character of the
+    //  text, by how common it is. Returns None if nothing allowed could be
+    //  found.
+
+    pub fn next_where<F: Fn(char) -> bool>(&mut self, allowed: F) -> Option<(Token, usize)> {
+        let stats = self.stats;
+        let key_length = self.current.chars().count();
+
+        for order in (1..cmp::min(self.current_order, key_length) + 1).rev() {
+            let key_start = match self.current.char_indices().nth(key_length - order) {
+                Some((offset, _)) => offset,
+                None => continue,
+            };
+            let choice_stats = match stats[order - 1].stats_for_state.get(&self.current[key_start..]) {
+                Some(choice_stats) => choice_stats,
+                None => continue,
+            };
+
+            let total = choice_stats.options
+                                    .iter()
+                                    .filter(|&(next_char, _)| allowed(*next_char))
+                                    .fold(0, |total, (_, next_count)| total + next_count);
+            if total == 0 {
+                continue;
+            }
+
+            self.update_order_used();
+
+            let mut choice_num = pick_random_in_range_with(&mut self.rng, 1, total);
+            for (next_char, next_count) in choice_stats.options.iter() {
+                if !allowed(*next_char) {
+                    continue;
                 }
+                choice_num -= *next_count;
 
-                return (*next_char, order_used);
+                if choice_num <= 0 {
+                    self.advance(*next_char);
+                    return Some((Token::from_char(*next_char), order));
+                }
             }
         }
 
-        panic!("Failed to choose a next character.");
+        let mut frequencies = stats[0].stats_for_state
+                                      .iter()
+                                      .filter_map(|(key, choice_stats)| {
+                                          key.chars()
+                                             .next()
+                                             .map(|c| (c, choice_stats.total_usages))
+                                      })
+                                      .filter(|&(c, _)| allowed(c))
+                                      .collect::<Vec<_>>();
+        frequencies.sort();
+        let total = frequencies.iter().map(|&(_, count)| count).sum::<i32>();
+        if total == 0 {
+            return None;
+        }
+
+        let mut choice_num = pick_random_in_range_with(&mut self.rng, 1, total);
+        for (next_char, count) in frequencies {
+            choice_num -= count;
+            if choice_num <= 0 {
+                self.advance(next_char);
+                return Some((Token::from_char(next_char), 0));
+            }
+        }
+        None
+    }
+
+    fn advance(&mut self, next_char: char) {
+        self.current.push(next_char);
+        self.sentences.push(next_char);
+        self.total += 1;
+        if self.chapter_plan.token == Some(next_char) {
+            self.chapter_breaks += 1;
+        }
+
+        let remove_count = cmp::max(self.current.chars().count() - self.current_order, 0);
+        for _ in 0..remove_count {
+            self.current.remove(0);
+        }
     }
 }
 
@@ -183,13 +473,78 @@ fn debug_stats(stats: &OrderStats) {
     }
 }
 
+thread_local!(static RANDOM: RefCell<XorShiftRng> = RefCell::new(XorShiftRng::new_unseeded()));
+
+// Seed the random numbers used by pick_random_in_range on the current thread.
+
+pub fn seed_random(seed: u32) {
+    RANDOM.with(|rng| *rng.borrow_mut() = create_rng(seed, 0));
+}
+
+// Create a random number generator for one stream of a seed. Each stream
+//  gives a different sequence, so that parallel workers can draw from their
+//  own streams and still be reproducible.
+
+pub fn create_rng(seed: u32, stream: u32) -> XorShiftRng {
+    let mut rng = XorShiftRng::from_seed([seed ^ 0x193a6754,
+                                          stream.wrapping_mul(0x9e3779b9) ^ 0xa8a7d469,
+                                          0x97830e05,
+                                          0x113ba7bb]);
+
+    // Neighbouring seeds start out with similar sequences; mix them up a bit.
+    for _ in 0..32 {
+        rng.next_u32();
+    }
+
+    rng
+}
+
 pub fn pick_random_in_range<T: NumCast>(start: T, end: T) -> T {
+    RANDOM.with(|rng| pick_random_in_range_with(&mut *rng.borrow_mut(), start, end))
+}
+
+pub fn pick_random_in_range_with<T: NumCast, R: Rng>(rng: &mut R, start: T, end: T) -> T {
     let start_f = num::cast::<T, f64>(start).unwrap();
     let end_f = num::cast::<T, f64>(end).unwrap();
 
     let multiplier = end_f - start_f + 1.0;
-    let r = rand::random::<f64>();
+    let r = rng.gen::<f64>();
     let result = start_f + (multiplier * r);
 
-    return num::cast::<f64, T>(result).unwrap();
+    num::cast::<f64, T>(result).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use headings::HeadingKind;
+
+    #[test]
+    fn chapter_breaks_are_steered_whatever_the_distortion_factor() {
+        let heading = Structure::Heading(HeadingKind::Chapter).token();
+        let stats = Vec::new();
+        let mut options = BTreeMap::new();
+        options.insert('T', 9);
+        options.insert(heading, 1);
+        let choice_stats = CharChoiceStats {
+            total_usages: 10,
+            options,
+        };
+
+        for distortion_factor in 0..3 {
+            let mut args = test_args();
+            args.distortion_factor = distortion_factor;
+            args.chapter_plan = ChapterPlan::new(HeadingKind::Chapter, &[1000], 2000, Some(2));
+            let mut generator = Generator::new(&stats, &args, 1, 4);
+
+            // Too early for the planned break: ruled out.
+            generator.calculate_distortions(&choice_stats);
+            assert_eq!(generator.distortions.options.get(&heading), Some(&0));
+
+            // At the planned break: made likely.
+            generator.total = 1000;
+            generator.calculate_distortions(&choice_stats);
+            assert!(generator.distortions.options[&heading] >= 10);
+        }
+    }
 }
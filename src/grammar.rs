@@ -0,0 +1,721 @@
+use std::cmp;
+use std::collections::{BTreeMap, BTreeSet};
+use generate_text::pick_random_in_range;
+
+// How deeply rules may nest before expansion steers toward the alternatives
+//  which finish soonest, so that recursive rules always finish.
+const MAX_DEPTH: usize = 12;
+
+// How many times a word is picked for a symbol which comes up again in an
+//  expansion before settling for one it has already been expanded to.
+const MAX_REPEAT_TRIES: usize = 10;
+
+// Words which title case leaves in lower case, unless they come first or last.
+const SMALL_WORDS: [&str; 17] = ["a", "an", "the", "and", "but", "or", "nor", "of",
+                                 "in", "on", "at", "to", "by", "for", "from", "with",
+                                 "as"];
+
+// Changes made to a symbol's expansion, written after its name:
+//  "#person.possessive#".
+#[derive(Debug,Eq,PartialEq,Copy,Clone)]
+enum Modifier {
+    // "Alice" becomes "Alice's", "Achilles" becomes "Achilles'".
+    Possessive,
+    // "tart" becomes "tarts", "story" becomes "stories".
+    Plural,
+    // With an article which agrees with it: "an egg", "a unicorn".
+    A,
+    Capitalize,
+    TitleCase,
+    Upper,
+    Lower,
+}
+
+const MODIFIERS: [(&str, Modifier); 7] = [("possessive", Modifier::Possessive),
+                                           ("plural", Modifier::Plural),
+                                           ("a", Modifier::A),
+                                           ("capitalize", Modifier::Capitalize),
+                                           ("title", Modifier::TitleCase),
+                                           ("upper", Modifier::Upper),
+                                           ("lower", Modifier::Lower)];
+
+#[derive(Debug,Clone)]
+enum Element {
+    Text(String),
+    Symbol(String, Vec<Modifier>),
+    // Included half of the time: "(#adjective# )".
+    Optional(Vec<Element>),
+}
+
+#[derive(Debug,Clone)]
+struct Alternative {
+    weight: usize,
+    elements: Vec<Element>,
+}
+
+// A context free grammar in the style of Tracery. Each rule has a name and
+//  alternatives to choose between, which mix text with symbols (the names
+//  of other rules, between '#'s) that expand in turn:
+//
+//    title = #article# (#adjective# )#thing# | #person.possessive# #thing# ^2
+//
+// Parts between parentheses are optional, and an alternative ending in
+//  "^n" is n times as likely to be chosen as one without. A '\' makes the
+//  character after it plain text.
+#[derive(Clone)]
+pub struct Grammar {
+    rules: BTreeMap<String, Vec<Alternative>>,
+}
+
+impl Grammar {
+    pub fn new() -> Grammar {
+        Grammar { rules: BTreeMap::new() }
+    }
+
+    pub fn has_rule(&self, name: &str) -> bool {
+        self.rules.contains_key(name)
+    }
+
+    // Whether one of a rule's alternatives is the given plain word (in any
+    //  case).
+
+    pub fn has_word(&self, name: &str, word: &str) -> bool {
+        self.rules.get(name).is_some_and(|alternatives| {
+            alternatives.iter().any(|alternative| {
+                match alternative.elements[..] {
+                    [Element::Text(ref text)] => text.to_lowercase() == word.to_lowercase(),
+                    _ => false,
+                }
+            })
+        })
+    }
+
+    // Add the rules of another grammar which this one doesn't have.
+
+    pub fn add_missing_rules(&mut self, other: &Grammar) {
+        for (name, alternatives) in other.rules.iter() {
+            if !self.rules.contains_key(name) {
+                self.rules.insert(name.clone(), alternatives.clone());
+            }
+        }
+    }
+
+    // The alternatives of a rule which are plain words.
+
+    pub fn words(&self, name: &str) -> Vec<String> {
+        let mut words = Vec::new();
+        if let Some(alternatives) = self.rules.get(name) {
+            for alternative in alternatives.iter() {
+                if let [Element::Text(ref text)] = alternative.elements[..] {
+                    words.push(text.clone());
+                }
+            }
+        }
+        words
+    }
+
+    // The number of alternatives a rule has.
+
+    pub fn alternative_count(&self, name: &str) -> usize {
+        self.rules.get(name).map_or(0, |alternatives| alternatives.len())
+    }
+
+    // Add alternatives to a rule from its definition: alternatives separated
+    //  by '|'.
+
+    pub fn add_rule(&mut self, name: &str, definition: &str) -> Result<(), String> {
+        let mut alternatives = Vec::new();
+        for alternative in split_alternatives(definition).iter() {
+            alternatives.push(parse_alternative(alternative)?);
+        }
+        self.rules.entry(String::from(name)).or_default().extend(alternatives);
+        Ok(())
+    }
+
+    // Add plain words as alternatives of a rule, with nothing in them
+    //  expanded.
+
+    pub fn add_words<'b, I: Iterator<Item = &'b str>>(&mut self, name: &str, words: I) {
+        let alternatives = self.rules.entry(String::from(name)).or_default();
+        for word in words {
+            alternatives.push(Alternative {
+                weight: 1,
+                elements: vec![Element::Text(String::from(word))],
+            });
+        }
+    }
+
+    // Replace all the alternatives of a rule with plain words.
+
+    pub fn set_words<'b, I: Iterator<Item = &'b str>>(&mut self, name: &str, words: I) {
+        self.rules.remove(name);
+        self.add_words(name, words);
+    }
+
+    // Leave out the alternatives which need a rule that isn't defined or can
+    //  never finish expanding (even in an optional part), and the rules left
+    //  with no alternatives, so that what remains is valid.
+
+    pub fn drop_unfinished(&mut self) {
+        loop {
+            let before = self.rules.values().map(|alternatives| alternatives.len()).sum::<usize>();
+            let finishing = self.finishing_heights()
+                                .keys()
+                                .map(|&name| String::from(name))
+                                .collect::<BTreeSet<_>>();
+            for alternatives in self.rules.values_mut() {
+                alternatives.retain(|alternative| {
+                    let mut symbols = Vec::new();
+                    collect_symbols(&alternative.elements, &mut symbols);
+                    symbols.iter().all(|symbol| finishing.contains(*symbol))
+                });
+            }
+            self.rules.retain(|_, alternatives| !alternatives.is_empty());
+
+            let after = self.rules.values().map(|alternatives| alternatives.len()).sum::<usize>();
+            if after == before {
+                break;
+            }
+        }
+    }
+
+    // Check that every symbol refers to a rule, and that every rule can be
+    //  expanded into text without going on forever.
+
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, alternatives) in self.rules.iter() {
+            for alternative in alternatives.iter() {
+                let mut symbols = Vec::new();
+                collect_symbols(&alternative.elements, &mut symbols);
+                for symbol in symbols.iter() {
+                    if !self.rules.contains_key(*symbol) {
+                        return Err(format!("The rule '{}' refers to '{}', which isn't defined.",
+                                           name,
+                                           symbol));
+                    }
+                }
+            }
+        }
+
+        let heights = self.finishing_heights();
+        if let Some(name) = self.rules.keys().find(|name| !heights.contains_key(&name[..])) {
+            return Err(format!("The rule '{}' can never finish expanding.", name));
+        }
+
+        Ok(())
+    }
+
+    // How deep the shallowest complete expansion of each rule which can
+    //  finish goes. A rule finishes if one of its alternatives only needs
+    //  rules which finish (optional parts can always be left out), and its
+    //  height is one more than the highest of those rules, for its lowest
+    //  such alternative.
+
+    fn finishing_heights(&self) -> BTreeMap<&str, usize> {
+        let mut heights = BTreeMap::new();
+        loop {
+            let before = heights.len();
+            for (name, alternatives) in self.rules.iter() {
+                if heights.contains_key(&name[..]) {
+                    continue;
+                }
+                let height = alternatives.iter()
+                                         .filter_map(|alternative| {
+                                             alternative_height(alternative, &heights)
+                                         })
+                                         .min();
+                if let Some(height) = height {
+                    heights.insert(&name[..], height);
+                }
+            }
+            if heights.len() == before {
+                break;
+            }
+        }
+        heights
+    }
+
+    // Expand a rule into text. Articles are made to agree with the word after
+    //  them, and spaces are tidied up. A symbol which comes up more than once
+    //  (as in "For #name# and #name#") is given a different word each time,
+    //  where it can be.
+
+    pub fn expand(&self, name: &str) -> String {
+        let mut output = String::new();
+        let mut used = BTreeSet::new();
+        self.expand_symbol(name, &[], 0, &mut used, &mut output);
+        agree_articles(&tidy_spaces(&output))
+    }
+
+    fn expand_symbol(&self,
+                     name: &str,
+                     modifiers: &[Modifier],
+                     depth: usize,
+                     used: &mut BTreeSet<(String, String)>,
+                     output: &mut String) {
+        let alternatives = match self.rules.get(name) {
+            Some(alternatives) if !alternatives.is_empty() => alternatives,
+            _ => return,
+        };
+
+        let mut alternative = match self.pick_alternative(alternatives, depth) {
+            Some(alternative) => alternative,
+            None => return,
+        };
+        for _ in 1..MAX_REPEAT_TRIES {
+            let is_used = match alternative.elements[..] {
+                [Element::Text(ref text)] => used.contains(&(String::from(name), text.clone())),
+                _ => false,
+            };
+            if !is_used || depth >= MAX_DEPTH {
+                break;
+            }
+            alternative = pick_weighted(alternatives);
+        }
+        if let [Element::Text(ref text)] = alternative.elements[..] {
+            used.insert((String::from(name), text.clone()));
+        }
+
+        let mut expansion = String::new();
+        self.expand_elements(&alternative.elements, depth, used, &mut expansion);
+        for modifier in modifiers.iter() {
+            expansion = apply_modifier(*modifier, &expansion);
+        }
+        output.push_str(&expansion);
+    }
+
+    // Past the maximum depth, the alternative which finishes soonest is
+    //  taken, so that every rule it needs is lower than this one and the
+    //  expansion comes to an end.
+
+    fn pick_alternative<'b>(&self,
+                            alternatives: &'b [Alternative],
+                            depth: usize)
+                            -> Option<&'b Alternative> {
+        if depth >= MAX_DEPTH {
+            let heights = self.finishing_heights();
+            alternatives.iter()
+                        .filter_map(|alternative| {
+                            alternative_height(alternative, &heights)
+                                .map(|height| (height, alternative))
+                        })
+                        .min_by_key(|&(height, _)| height)
+                        .map(|(_, alternative)| alternative)
+        } else {
+            Some(pick_weighted(alternatives))
+        }
+    }
+
+    fn expand_elements(&self,
+                       elements: &[Element],
+                       depth: usize,
+                       used: &mut BTreeSet<(String, String)>,
+                       output: &mut String) {
+        for element in elements.iter() {
+            match *element {
+                Element::Text(ref text) => output.push_str(text),
+                Element::Symbol(ref name, ref modifiers) => {
+                    self.expand_symbol(name, modifiers, depth + 1, used, output)
+                }
+                Element::Optional(ref elements) => {
+                    if depth < MAX_DEPTH && pick_random_in_range(0, 1) == 0 {
+                        self.expand_elements(elements, depth, used, output);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn pick_weighted(alternatives: &[Alternative]) -> &Alternative {
+    let total = alternatives.iter().fold(0, |total, alternative| total + alternative.weight);
+    let mut choice = pick_random_in_range(1, total);
+    for alternative in alternatives.iter() {
+        if choice <= alternative.weight {
+            return alternative;
+        }
+        choice -= alternative.weight;
+    }
+    alternatives.last().unwrap()
+}
+
+fn collect_symbols<'b>(elements: &'b [Element], symbols: &mut Vec<&'b str>) {
+    for element in elements.iter() {
+        match *element {
+            Element::Text(_) => (),
+            Element::Symbol(ref name, _) => symbols.push(name),
+            Element::Optional(ref elements) => collect_symbols(elements, symbols),
+        }
+    }
+}
+
+// The height of an alternative given the heights of the rules which finish:
+//  one more than the highest rule it needs, or None if it needs one which
+//  doesn't finish.
+
+fn alternative_height(alternative: &Alternative,
+                      heights: &BTreeMap<&str, usize>)
+                      -> Option<usize> {
+    let mut symbols = Vec::new();
+    collect_required_symbols(&alternative.elements, &mut symbols);
+    let mut height = 0;
+    for symbol in symbols {
+        height = cmp::max(height, heights.get(symbol)? + 1);
+    }
+    Some(height)
+}
+
+fn collect_required_symbols<'b>(elements: &'b [Element], symbols: &mut Vec<&'b str>) {
+    for element in elements.iter() {
+        if let Element::Symbol(ref name, _) = *element {
+            symbols.push(name);
+        }
+    }
+}
+
+// Split a definition at the '|'s which aren't escaped.
+
+fn split_alternatives(definition: &str) -> Vec<String> {
+    let mut alternatives = vec![String::new()];
+    let mut chars = definition.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                alternatives.last_mut().unwrap().push(c);
+                if let Some(escaped) = chars.next() {
+                    alternatives.last_mut().unwrap().push(escaped);
+                }
+            }
+            '|' => alternatives.push(String::new()),
+            _ => alternatives.last_mut().unwrap().push(c),
+        }
+    }
+    alternatives
+}
+
+fn parse_alternative(alternative: &str) -> Result<Alternative, String> {
+    let mut text = alternative.trim();
+    let mut weight = 1;
+    if let Some(caret) = text.rfind('^') {
+        if let Ok(parsed) = text[caret + 1..].trim().parse::<usize>() {
+            if parsed == 0 {
+                return Err(format!("The alternative '{}' has a weight of 0.", text));
+            }
+            weight = parsed;
+            text = text[..caret].trim_end();
+        }
+    }
+
+    let mut chars = text.chars().peekable();
+    let elements = parse_elements(&mut chars, false)?;
+    if chars.next().is_some() {
+        return Err(format!("Unmatched ')' in '{}'.", text));
+    }
+    if elements.is_empty() {
+        return Err(String::from("An alternative is empty."));
+    }
+
+    Ok(Alternative {
+        weight,
+        elements,
+    })
+}
+
+fn parse_elements<I: Iterator<Item = char>>(chars: &mut ::std::iter::Peekable<I>,
+                                            in_optional: bool)
+                                            -> Result<Vec<Element>, String> {
+    let mut elements = Vec::new();
+    let mut text = String::new();
+
+    loop {
+        let c = match chars.peek() {
+            Some(&c) => c,
+            None if in_optional => return Err(String::from("Unmatched '('.")),
+            None => break,
+        };
+        if c == ')' {
+            if in_optional {
+                chars.next();
+            }
+            break;
+        }
+        chars.next();
+
+        match c {
+            '\\' => {
+                if let Some(escaped) = chars.next() {
+                    text.push(escaped);
+                }
+            }
+            '#' => {
+                let mut symbol = String::new();
+                loop {
+                    match chars.next() {
+                        Some('#') => break,
+                        Some(c) => symbol.push(c),
+                        None => return Err(format!("Unmatched '#' before '{}'.", symbol)),
+                    }
+                }
+                if !text.is_empty() {
+                    elements.push(Element::Text(text.clone()));
+                    text.clear();
+                }
+                elements.push(parse_symbol(&symbol)?);
+            }
+            '(' => {
+                if !text.is_empty() {
+                    elements.push(Element::Text(text.clone()));
+                    text.clear();
+                }
+                elements.push(Element::Optional(parse_elements(chars, true)?));
+            }
+            _ => text.push(c),
+        }
+    }
+
+    if !text.is_empty() {
+        elements.push(Element::Text(text));
+    }
+    Ok(elements)
+}
+
+fn parse_symbol(symbol: &str) -> Result<Element, String> {
+    let mut parts = symbol.split('.');
+    let name = parts.next().unwrap().trim();
+    if name.is_empty() {
+        return Err(String::from("A symbol has no name."));
+    }
+
+    let mut modifiers = Vec::new();
+    for part in parts {
+        match MODIFIERS.iter().find(|&&(modifier_name, _)| modifier_name == part) {
+            Some(&(_, modifier)) => modifiers.push(modifier),
+            None => {
+                let names = MODIFIERS.iter().map(|&(name, _)| name).collect::<Vec<_>>();
+                return Err(format!("Unknown modifier '{}' on '{}'. Known modifiers: {}.",
+                                   part,
+                                   name,
+                                   names.join(", ")));
+            }
+        }
+    }
+
+    Ok(Element::Symbol(String::from(name), modifiers))
+}
+
+fn apply_modifier(modifier: Modifier, text: &str) -> String {
+    match modifier {
+        Modifier::Possessive => possessive(text),
+        Modifier::Plural => on_last_word(text, plural),
+        Modifier::A => format!("{} {}", article_for(text), text),
+        Modifier::Capitalize => capitalize(text),
+        Modifier::TitleCase => title_case(text),
+        Modifier::Upper => text.to_uppercase(),
+        Modifier::Lower => text.to_lowercase(),
+    }
+}
+
+// Apply a change to the last word of some text, or the last part of a
+//  hyphenated word ("Tea-Party" to "Tea-Parties").
+
+fn on_last_word<F: Fn(&str) -> String>(text: &str, change: F) -> String {
+    match text.rfind([' ', '-']) {
+        Some(split) => format!("{}{}", &text[..split + 1], change(&text[split + 1..])),
+        None => change(text),
+    }
+}
+
+fn possessive(text: &str) -> String {
+    if text.is_empty() || text.ends_with("'s") || text.ends_with('\'') {
+        String::from(text)
+    } else if text.ends_with('s') {
+        format!("{}'", text)
+    } else {
+        format!("{}'s", text)
+    }
+}
+
+// Nouns with irregular plurals, in lower case.
+const IRREGULAR_PLURALS: [(&str, &str); 13] = [("man", "men"),
+                                               ("woman", "women"),
+                                               ("child", "children"),
+                                               ("mouse", "mice"),
+                                               ("dormouse", "dormice"),
+                                               ("foot", "feet"),
+                                               ("tooth", "teeth"),
+                                               ("goose", "geese"),
+                                               ("person", "people"),
+                                               ("ox", "oxen"),
+                                               ("sheep", "sheep"),
+                                               ("fish", "fish"),
+                                               ("deer", "deer")];
+
+fn plural(word: &str) -> String {
+    let lowercase = word.to_lowercase();
+    for &(singular, plural) in IRREGULAR_PLURALS.iter() {
+        if lowercase == singular || lowercase.ends_with(&format!("-{}", singular)) {
+            let stem = &word[..word.len() - singular.len()];
+            let ending = &word[word.len() - singular.len()..];
+            let plural = if ending.chars().next().is_some_and(|c| c.is_uppercase()) {
+                capitalize(plural)
+            } else {
+                String::from(plural)
+            };
+            return format!("{}{}", stem, plural);
+        }
+    }
+
+    let before_y = lowercase.chars().rev().nth(1);
+    if lowercase.ends_with('y') && before_y.is_some_and(|c| !"aeiou".contains(c)) {
+        return format!("{}ies", &word[..word.len() - 1]);
+    }
+    if ["s", "x", "z", "ch", "sh"].iter().any(|ending| lowercase.ends_with(ending)) {
+        return format!("{}es", word);
+    }
+    format!("{}s", word)
+}
+
+// Whether a word takes "an" rather than "a": it starts with a vowel sound.
+
+fn article_for(word: &str) -> &'static str {
+    let lowercase = word.trim_start_matches(|c: char| !c.is_alphanumeric()).to_lowercase();
+    let silent_h = ["hour", "honest", "honour", "honor", "heir"];
+    let sounds_like_y = ["uni", "use", "usu", "uto", "eu", "one", "once"];
+
+    if silent_h.iter().any(|start| lowercase.starts_with(start)) {
+        return "an";
+    }
+    if sounds_like_y.iter().any(|start| lowercase.starts_with(start)) {
+        return "a";
+    }
+    match lowercase.chars().next() {
+        Some('a') | Some('e') | Some('i') | Some('o') | Some('u') => "an",
+        _ => "a",
+    }
+}
+
+// Make each "a" or "an" agree with the word after it.
+
+fn agree_articles(text: &str) -> String {
+    let words = text.split(' ').collect::<Vec<_>>();
+    let mut output = Vec::new();
+    for (i, word) in words.iter().enumerate() {
+        let next = words.get(i + 1);
+        let agreed = match (*word, next) {
+            ("a", Some(next)) | ("an", Some(next)) => String::from(article_for(next)),
+            ("A", Some(next)) | ("An", Some(next)) => capitalize(article_for(next)),
+            _ => String::from(*word),
+        };
+        output.push(agreed);
+    }
+    output.join(" ")
+}
+
+// Collapse runs of spaces (left by optional parts that weren't included), and
+//  remove spaces before punctuation.
+
+fn tidy_spaces(text: &str) -> String {
+    let mut output = String::new();
+    for word in text.split_whitespace() {
+        let punctuation = word.chars().all(|c| ",.;:!?".contains(c));
+        if !output.is_empty() && !punctuation {
+            output.push(' ');
+        }
+        output.push_str(word);
+    }
+    output
+}
+
+pub fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+// Capitalize every word (and every part of a hyphenated word), apart from
+//  small words like "of" and "the" in the middle.
+
+pub fn title_case(text: &str) -> String {
+    let words = text.split(' ').collect::<Vec<_>>();
+    let last = words.len() - 1;
+    words.iter()
+         .enumerate()
+         .map(|(i, word)| {
+             if i > 0 && i < last && SMALL_WORDS.contains(&&word.to_lowercase()[..]) {
+                 word.to_lowercase()
+             } else {
+                 word.split('-').map(capitalize).collect::<Vec<_>>().join("-")
+             }
+         })
+         .collect::<Vec<_>>()
+         .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use generate_text::seed_random;
+
+    fn grammar(rules: &[(&str, &str)]) -> Grammar {
+        let mut grammar = Grammar::new();
+        for &(name, definition) in rules.iter() {
+            grammar.add_rule(name, definition).unwrap();
+        }
+        grammar
+    }
+
+    #[test]
+    fn recursive_rules_finish_past_the_maximum_depth() {
+        let grammar = grammar(&[("x", "#x# | #y# #y#"), ("y", "why")]);
+        assert!(grammar.validate().is_ok());
+        for _ in 0..20 {
+            assert_eq!(grammar.expand("x"), "why why");
+        }
+    }
+
+    #[test]
+    fn repeated_symbols_get_different_words() {
+        let grammar = grammar(&[("dedication", "For #name# and #name#"),
+                                ("name", "Alice | Dinah")]);
+        seed_random(1);
+        for _ in 0..20 {
+            let dedication = grammar.expand("dedication");
+            assert!(dedication == "For Alice and Dinah" || dedication == "For Dinah and Alice",
+                    "{}",
+                    dedication);
+        }
+    }
+
+    #[test]
+    fn undefined_and_endless_rules_are_invalid() {
+        assert!(grammar(&[("title", "#thing#")]).validate().is_err());
+        assert!(grammar(&[("x", "#y#"), ("y", "(#z# )#x#"), ("z", "zed")]).validate().is_err());
+        assert!(grammar(&[("x", "#y#"), ("y", "(#x# )why")]).validate().is_ok());
+    }
+
+    #[test]
+    fn modifiers_and_articles_apply() {
+        let grammar = grammar(&[("title", "#person.possessive# #thing.plural#"),
+                                ("person", "Achilles"),
+                                ("thing", "story"),
+                                ("egg", "a #adjective# egg"),
+                                ("adjective", "ostrich")]);
+        assert_eq!(grammar.expand("title"), "Achilles' stories");
+        assert_eq!(grammar.expand("egg"), "an ostrich egg");
+    }
+
+    #[test]
+    fn escapes_and_weights_parse() {
+        let grammar = grammar(&[("sign", "a \\| b ^3 | c")]);
+        assert_eq!(grammar.alternative_count("sign"), 2);
+        assert!(Grammar::new().add_rule("bad", "x ^0").is_err());
+    }
+
+    #[test]
+    fn title_case_leaves_small_words() {
+        assert_eq!(title_case("the pool of tears"), "The Pool of Tears");
+        assert_eq!(title_case("down the rabbit-hole"), "Down the Rabbit-Hole");
+    }
+}
@@ -0,0 +1,146 @@
+use regex::Regex;
+
+// Something that was stripped from a text, for reporting.
+pub struct Removal {
+    pub description: &'static str,
+    pub lines: usize,
+}
+
+// Remove Project Gutenberg front and back matter from a text: everything up to
+//  the START marker and from the END marker on, plus paragraphs that are
+//  production credits, transcriber's notes or license text.
+
+pub fn strip_boilerplate(text: &str) -> (String, Vec<Removal>) {
+    let start_pattern = Regex::new(r"(?xi) ^\s* ( \*{3}\s*START\s+OF\s+(THE|THIS)\s+PROJECT\s+GUTENBERG
+                                        | \*END\*\s*THE\s+SMALL\s+PRINT )")
+                            .unwrap();
+    let end_pattern = Regex::new(r"(?xi) ^\s* ( \*{3}\s*END\s+OF\s+(THE|THIS)\s+PROJECT\s+GUTENBERG
+                                      | END\s+OF\s+(THE\s+)?PROJECT\s+GUTENBERG )")
+                          .unwrap();
+    let produced_by_pattern = Regex::new(r"(?xi) ^\s* ( PRODUCED\s+BY
+                                              | E-?TEXT\s+PREPARED\s+BY
+                                              | TRANSCRIBED\s+(FROM\s.*\s)?BY )")
+                                  .unwrap();
+    let transcriber_pattern = Regex::new(r"(?i)^\s*\[?\s*TRANSCRIBER'?S'? NOTES?").unwrap();
+    let license_pattern = Regex::new(r"(?i)PROJECT GUTENBERG|GUTENBERG\.(ORG|NET)").unwrap();
+
+    let lines = text.lines().collect::<Vec<_>>();
+    let mut keep = vec![true; lines.len()];
+    let mut removals = Vec::new();
+
+    if let Some(start) = lines.iter().position(|line| start_pattern.is_match(line)) {
+        remove_lines(&mut keep, 0, start + 1);
+        removals.push(Removal {
+            description: "header up to the START marker",
+            lines: start + 1,
+        });
+    }
+
+    if let Some(end) = lines.iter().rposition(|line| end_pattern.is_match(line)) {
+        if keep[end] {
+            remove_lines(&mut keep, end, lines.len());
+            removals.push(Removal {
+                description: "footer from the END marker",
+                lines: lines.len() - end,
+            });
+        }
+    }
+
+    // Look at what's left paragraph by paragraph:
+
+    let mut paragraph_start = 0;
+    while paragraph_start < lines.len() {
+        if !keep[paragraph_start] || lines[paragraph_start].trim().is_empty() {
+            paragraph_start += 1;
+            continue;
+        }
+
+        let mut paragraph_end = paragraph_start;
+        while paragraph_end < lines.len() && keep[paragraph_end] &&
+              !lines[paragraph_end].trim().is_empty() {
+            paragraph_end += 1;
+        }
+
+        let first_line = lines[paragraph_start];
+        let description = if produced_by_pattern.is_match(first_line) {
+            Some("production credit")
+        } else if transcriber_pattern.is_match(first_line) {
+            Some("transcriber's note")
+        } else if lines[paragraph_start..paragraph_end]
+                      .iter()
+                      .any(|line| license_pattern.is_match(line)) {
+            Some("license paragraph")
+        } else {
+            None
+        };
+
+        if let Some(description) = description {
+            remove_lines(&mut keep, paragraph_start, paragraph_end);
+            removals.push(Removal {
+                description,
+                lines: paragraph_end - paragraph_start,
+            });
+        }
+
+        paragraph_start = paragraph_end;
+    }
+
+    if removals.is_empty() {
+        return (String::from(text), removals);
+    }
+
+    let mut output = String::new();
+    for (line, _) in lines.iter().zip(keep.iter()).filter(|&(_, keep)| *keep) {
+        output.push_str(line);
+        output.push('\n');
+    }
+
+    // Removing the front and back matter tends to leave blank lines behind.
+    let trimmed = output.trim_matches('\n');
+    let mut stripped = String::from(trimmed);
+    stripped.push('\n');
+
+    (stripped, removals)
+}
+
+fn remove_lines(keep: &mut [bool], start: usize, end: usize) {
+    for line_keep in keep[start..end].iter_mut() {
+        *line_keep = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn front_and_back_matter_are_stripped() {
+        let text = "The Project Gutenberg EBook of Alice\n\n\
+                    *** START OF THIS PROJECT GUTENBERG EBOOK ALICE ***\n\n\
+                    Produced by Someone\n\n\
+                    CHAPTER I\n\nAlice was beginning to get very tired.\n\n\
+                    [Transcriber's Note: spelling kept.]\n\n\
+                    THE END\n\n\
+                    *** END OF THIS PROJECT GUTENBERG EBOOK ALICE ***\n\nLicense text.\n";
+        let (stripped, removals) = strip_boilerplate(text);
+        // The blank lines around a removed paragraph are left to the breaks
+        //  pass to collapse.
+        assert_eq!(stripped,
+                   "CHAPTER I\n\nAlice was beginning to get very tired.\n\n\nTHE END\n");
+        let descriptions = removals.iter().map(|removal| removal.description).collect::<Vec<_>>();
+        assert_eq!(descriptions,
+                   vec!["header up to the START marker",
+                        "footer from the END marker",
+                        "production credit",
+                        "transcriber's note"]);
+        assert_eq!(removals[0].lines, 3);
+    }
+
+    #[test]
+    fn texts_without_boilerplate_are_left_alone() {
+        let text = "Once upon a time.\n\nThe end.";
+        let (stripped, removals) = strip_boilerplate(text);
+        assert_eq!(stripped, text);
+        assert!(removals.is_empty());
+    }
+}
@@ -0,0 +1,534 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use regex::Regex;
+use title_generator::TitlePieces;
+use structure::Structure;
+
+// The kinds of structural headings found in books. In preprocessed text a
+//  heading is replaced by the structure token for its kind.
+
+#[derive(Debug,Eq,PartialEq,Copy,Clone)]
+pub enum HeadingKind {
+    Part,
+    Chapter,
+    Section,
+    Aphorism,
+}
+
+impl HeadingKind {
+    pub fn parse(name: &str) -> Option<HeadingKind> {
+        match name {
+            "part" => Some(HeadingKind::Part),
+            "chapter" => Some(HeadingKind::Chapter),
+            "section" => Some(HeadingKind::Section),
+            "aphorism" => Some(HeadingKind::Aphorism),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug,Eq,PartialEq,Copy,Clone)]
+pub enum Numbering {
+    Roman,
+    Arabic,
+    // Spelled out in English: "Twenty-Three".
+    Words,
+}
+
+impl Numbering {
+    pub fn parse(name: &str) -> Option<Numbering> {
+        match name {
+            "roman" => Some(Numbering::Roman),
+            "arabic" => Some(Numbering::Arabic),
+            "words" => Some(Numbering::Words),
+            _ => None,
+        }
+    }
+}
+
+// Where a generated title goes, if the heading has one.
+#[derive(Debug,Eq,PartialEq,Copy,Clone)]
+pub enum TitleStyle {
+    NoTitle,
+    NextParagraph,
+    SameLine,
+}
+
+impl TitleStyle {
+    pub fn parse(name: &str) -> Option<TitleStyle> {
+        match name {
+            "none" => Some(TitleStyle::NoTitle),
+            "next-paragraph" => Some(TitleStyle::NextParagraph),
+            "same-line" => Some(TitleStyle::SameLine),
+            _ => None,
+        }
+    }
+}
+
+// How to recognize one kind of heading in a source text, and how to render
+//  it in the same style in generated text. The title of a heading in the
+//  source, if it has one, is the pattern's group named "title".
+#[derive(Debug,Clone)]
+pub struct HeadingRule {
+    pub kind: HeadingKind,
+    pub pattern: Regex,
+    pub label: String,
+    pub numbering: Numbering,
+    pub suffix: String,
+    pub title_style: TitleStyle,
+}
+
+// The heading rules for a corpus. The book opens with a heading of the
+//  primary kind, if there is one.
+#[derive(Debug,Clone)]
+pub struct HeadingPreset {
+    pub rules: Vec<HeadingRule>,
+    pub primary: Option<HeadingKind>,
+}
+
+impl HeadingPreset {
+    // The preset for a corpus without headings.
+
+    pub fn none() -> HeadingPreset {
+        HeadingPreset {
+            rules: Vec::new(),
+            primary: None,
+        }
+    }
+
+    // Parse a heading file. Each kind of heading is a line giving the kind,
+    //  how to number it and the label to number, if any, followed by
+    //  indented lines saying how to find it in the source text:
+    //
+    //    chapter roman CHAPTER
+    //        pattern CHAPTER .*\n\n(?P<title>.*)
+    //        title next-paragraph
+    //
+    // The pattern matches the whole heading; its group named "title" is the
+    //  title, if it has one. "title" says where a generated title goes (none,
+    //  next-paragraph or same-line; none by default), and "suffix" what
+    //  follows the number (in quotes if it ends in a space). The book opens
+    //  with a heading of the first kind given. Blank lines and lines
+    //  starting with '#' are ignored.
+
+    pub fn parse(config: &str) -> Result<HeadingPreset, String> {
+        // Each rule, and whether its pattern has been given yet.
+        let mut rules: Vec<(HeadingRule, bool)> = Vec::new();
+        let no_pattern = Regex::new("$^").unwrap();
+        for (line_number, line) in config.lines().enumerate() {
+            let indented = line.starts_with(char::is_whitespace);
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: String| format!("Line {}: {}", line_number + 1, message);
+
+            if !indented {
+                let mut words = line.splitn(3, char::is_whitespace);
+                let kind_name = words.next().unwrap();
+                let kind = HeadingKind::parse(kind_name).ok_or_else(|| {
+                    error(format!("unknown kind of heading '{}'.", kind_name))
+                })?;
+                if rules.iter().any(|(rule, _)| rule.kind == kind) {
+                    return Err(error(format!("'{}' is given twice.", kind_name)));
+                }
+                let numbering_name = words.next().unwrap_or("");
+                let numbering = Numbering::parse(numbering_name).ok_or_else(|| {
+                    error(format!("unknown numbering '{}' (roman, arabic or words).",
+                                  numbering_name))
+                })?;
+                let label = words.next().unwrap_or("").trim();
+
+                rules.push((HeadingRule {
+                    kind,
+                    pattern: no_pattern.clone(),
+                    label: String::from(label),
+                    numbering,
+                    suffix: String::new(),
+                    title_style: TitleStyle::NoTitle,
+                }, false));
+                continue;
+            }
+
+            let (rule, has_pattern) = rules.last_mut().ok_or_else(|| {
+                error(String::from("an indented line has no kind of heading above it."))
+            })?;
+            let mut words = line.splitn(2, char::is_whitespace);
+            let name = words.next().unwrap();
+            let value = words.next().unwrap_or("").trim();
+            match name {
+                "pattern" => {
+                    rule.pattern = Regex::new(value).map_err(|regex_error| {
+                        error(format!("invalid pattern: {}", regex_error))
+                    })?;
+                    *has_pattern = true;
+                }
+                "title" => {
+                    rule.title_style = TitleStyle::parse(value).ok_or_else(|| {
+                        error(format!("unknown title style '{}' (none, next-paragraph or \
+                                       same-line).",
+                                      value))
+                    })?;
+                }
+                "suffix" => {
+                    let quoted = value.len() >= 2 && value.starts_with('"') && value.ends_with('"');
+                    rule.suffix = String::from(if quoted { &value[1..value.len() - 1] } else { value });
+                }
+                _ => return Err(error(format!("unknown setting '{}'.", name))),
+            }
+        }
+
+        if let Some((rule, _)) = rules.iter().find(|&&(_, has_pattern)| !has_pattern) {
+            return Err(format!("No pattern is given for {:?} headings.", rule.kind));
+        }
+        let primary = rules.first().map(|(rule, _)| rule.kind);
+        Ok(HeadingPreset {
+            rules: rules.into_iter().map(|(rule, _)| rule).collect(),
+            primary,
+        })
+    }
+
+    pub fn rule_for(&self, kind: HeadingKind) -> Option<&HeadingRule> {
+        self.rules.iter().find(|rule| rule.kind == kind)
+    }
+
+    // The titles of the headings in a text, in order, for the rules whose
+    //  headings have titles.
+
+    pub fn collect_titles(&self, text: &str) -> Vec<String> {
+        let mut titles = Vec::new();
+        for rule in self.rules.iter() {
+            for captures in rule.pattern.captures_iter(text) {
+                let title = captures.name("title").map_or("", |title| title.trim());
+                if !title.is_empty() {
+                    titles.push(String::from(title));
+                }
+            }
+        }
+        titles
+    }
+
+    // The label and number of each heading in a text, in order, for each
+    //  rule: its words, leaving out its title.
+
+    pub fn heading_labels(&self, text: &str) -> Vec<String> {
+        let mut labels = Vec::new();
+        for rule in self.rules.iter() {
+            for captures in rule.pattern.captures_iter(text) {
+                let heading = captures.at(0).unwrap_or("");
+                let title = captures.name("title").unwrap_or("");
+                let label = heading.replacen(title, "", 1);
+                let words = label.split(|c: char| !c.is_alphanumeric())
+                                 .filter(|word| !word.is_empty())
+                                 .collect::<Vec<_>>();
+                labels.push(words.join(" "));
+            }
+        }
+        labels
+    }
+
+    // Replace each heading in the text with the token for its kind.
+
+    pub fn mark_headings(&self, text: &str) -> String {
+        let mut output = String::from(text);
+        for rule in self.rules.iter() {
+            let token = Structure::Heading(rule.kind).token().to_string();
+            output = rule.pattern.replace_all(&output, &token[..]);
+        }
+        output
+    }
+}
+
+// Renders the headings of generated text. Each kind of heading is numbered
+//  separately.
+pub struct HeadingRenderer<'a> {
+    preset: &'a HeadingPreset,
+    titles: &'a TitlePieces,
+    counts: Vec<(HeadingKind, usize)>,
+}
+
+impl<'a> HeadingRenderer<'a> {
+    pub fn new(preset: &'a HeadingPreset, titles: &'a TitlePieces) -> HeadingRenderer<'a> {
+        HeadingRenderer {
+            preset,
+            titles,
+            counts: Vec::new(),
+        }
+    }
+
+    // Whether headings of the given kind have titles.
+
+    pub fn has_title(&self, kind: HeadingKind) -> bool {
+        self.preset.rule_for(kind).is_some_and(|rule| rule.title_style != TitleStyle::NoTitle)
+    }
+
+    // Render the next heading of the given kind. Kinds that the preset has no
+    //  rule for render as nothing.
+
+    pub fn render(&mut self, kind: HeadingKind) -> Option<RenderedHeading> {
+        let titles = self.titles;
+        self.render_with(kind, || titles.generate_chapter_title())
+    }
+
+    // Render the next heading of the given kind, with a title about the text
+    //  of its chapter.
+
+    pub fn render_about(&mut self, kind: HeadingKind, chapter: &str) -> Option<RenderedHeading> {
+        let titles = self.titles;
+        self.render_with(kind, || titles.generate_chapter_title_about(chapter))
+    }
+
+    fn render_with<F: FnOnce() -> String>(&mut self,
+                                          kind: HeadingKind,
+                                          make_title: F)
+                                          -> Option<RenderedHeading> {
+        let rule = self.preset.rule_for(kind)?;
+
+        let count = match self.counts.iter_mut().find(|&&mut (counted, _)| counted == kind) {
+            Some(&mut (_, ref mut count)) => {
+                *count += 1;
+                *count
+            }
+            None => {
+                self.counts.push((kind, 1));
+                1
+            }
+        };
+
+        Some(rule.render(count, make_title))
+    }
+}
+
+// A heading rendered for generated text: its label and number ("CHAPTER
+//  IV"), and its title if it has one.
+pub struct RenderedHeading {
+    pub label: String,
+    pub title: Option<String>,
+    pub title_style: TitleStyle,
+}
+
+impl RenderedHeading {
+    // The heading as it would be laid out in a plain text book.
+
+    pub fn to_text(&self) -> String {
+        match (self.title_style, self.title.as_ref()) {
+            (TitleStyle::NextParagraph, Some(title)) => format!("{}\n\n{}", self.label, title),
+            (_, Some(title)) => format!("{}. {}", self.label, title),
+            (_, None) => self.label.clone(),
+        }
+    }
+
+    // The heading on a single line, for formats which mark headings up.
+
+    pub fn to_line(&self) -> String {
+        match self.title.as_ref() {
+            Some(title) if self.title_style == TitleStyle::NextParagraph => {
+                format!("{}: {}", self.label, title)
+            }
+            _ => self.to_text(),
+        }
+    }
+}
+
+impl HeadingRule {
+    pub fn render<F: FnOnce() -> String>(&self, number: usize, make_title: F) -> RenderedHeading {
+        let mut label = self.label.clone();
+        if !self.label.is_empty() {
+            label.push(' ');
+        }
+        let number = format_number(number, self.numbering);
+        // Spelled out numbers follow the label's capitals: "CHAPTER ONE",
+        //  "Chapter One".
+        let label_is_upper = self.label.chars().any(|c| c.is_uppercase()) &&
+                             !self.label.chars().any(|c| c.is_lowercase());
+        if label_is_upper {
+            label.push_str(&number.to_uppercase());
+        } else {
+            label.push_str(&number);
+        }
+        label.push_str(&self.suffix);
+
+        let title = match self.title_style {
+            TitleStyle::NoTitle => None,
+            TitleStyle::NextParagraph => Some(make_title()),
+            TitleStyle::SameLine => Some(make_title().to_uppercase()),
+        };
+
+        RenderedHeading {
+            label,
+            title,
+            title_style: self.title_style,
+        }
+    }
+}
+
+pub fn format_number(number: usize, numbering: Numbering) -> String {
+    match numbering {
+        Numbering::Roman => roman_numeral(number),
+        Numbering::Arabic => number.to_string(),
+        Numbering::Words => number_in_words(number),
+    }
+}
+
+// Roman numerals, with as many M's as it takes for numbers of 4000 and over.
+
+fn roman_numeral(number: usize) -> String {
+    let numerals = [(1000, "M"), (900, "CM"), (500, "D"), (400, "CD"), (100, "C"), (90, "XC"),
+                    (50, "L"), (40, "XL"), (10, "X"), (9, "IX"), (5, "V"), (4, "IV"), (1, "I")];
+    let mut output = String::new();
+    let mut remaining = number;
+    for &(value, numeral) in numerals.iter() {
+        while remaining >= value {
+            output.push_str(numeral);
+            remaining -= value;
+        }
+    }
+    output
+}
+
+const ONES: [&str; 20] = ["Zero", "One", "Two", "Three", "Four", "Five", "Six", "Seven",
+                          "Eight", "Nine", "Ten", "Eleven", "Twelve", "Thirteen",
+                          "Fourteen", "Fifteen", "Sixteen", "Seventeen", "Eighteen",
+                          "Nineteen"];
+const TENS: [&str; 10] = ["", "", "Twenty", "Thirty", "Forty", "Fifty", "Sixty",
+                          "Seventy", "Eighty", "Ninety"];
+const SCALES: [(usize, &str); 4] = [(1_000_000_000_000, "Trillion"),
+                                    (1_000_000_000, "Billion"),
+                                    (1_000_000, "Million"),
+                                    (1000, "Thousand")];
+
+// A number spelled out in English, the way a book numbers its chapters:
+//  "Twenty-Three", "One Hundred and Five".
+
+fn number_in_words(number: usize) -> String {
+    if number == 0 {
+        return String::from(ONES[0]);
+    }
+
+    let mut parts = Vec::new();
+    let mut remaining = number;
+    for &(scale, name) in SCALES.iter() {
+        if remaining >= scale {
+            parts.push(format!("{} {}", number_in_words(remaining / scale), name));
+            remaining %= scale;
+        }
+    }
+    if remaining >= 100 {
+        parts.push(format!("{} Hundred", ONES[remaining / 100]));
+        remaining %= 100;
+    }
+    if remaining > 0 {
+        let below_hundred = if remaining < 20 {
+            String::from(ONES[remaining])
+        } else if remaining.is_multiple_of(10) {
+            String::from(TENS[remaining / 10])
+        } else {
+            format!("{}-{}", TENS[remaining / 10], ONES[remaining % 10])
+        };
+        if parts.is_empty() {
+            parts.push(below_hundred);
+        } else {
+            parts.push(format!("and {}", below_hundred));
+        }
+    }
+    parts.join(" ")
+}
+
+// The heading file for a corpus sits next to it, with the extension
+//  ".headings".
+
+pub fn heading_file_for(corpus_file_name: &str) -> String {
+    let path = Path::new(corpus_file_name).with_extension("headings");
+    path.to_string_lossy().into_owned()
+}
+
+// Load the headings of a corpus from the given heading file, or from the
+//  corpus's own if none is given. A corpus without a heading file has no
+//  headings, as does the heading file "none".
+
+pub fn load_heading_preset(corpus_file_name: &str,
+                           heading_file_name: Option<&str>)
+                           -> Result<HeadingPreset, String> {
+    let (heading_file_name, given) = match heading_file_name {
+        Some("none") => return Ok(HeadingPreset::none()),
+        Some(heading_file_name) => (String::from(heading_file_name), true),
+        None => (heading_file_for(corpus_file_name), false),
+    };
+
+    let mut config = String::new();
+    match File::open(&heading_file_name) {
+        Ok(mut file) => {
+            file.read_to_string(&mut config)
+                .map_err(|error| format!("Could not read {}: {}", heading_file_name, error))?;
+        }
+        Err(_) if !given => return Ok(HeadingPreset::none()),
+        Err(error) => return Err(format!("Could not open {}: {}", heading_file_name, error)),
+    }
+
+    HeadingPreset::parse(&config).map_err(|message| {
+        format!("Invalid heading file {}: {}", heading_file_name, message)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heading_files_give_patterns_and_styles() {
+        let config = "# Chapters, then aphorisms.\n\
+                      chapter words Chapter\n    pattern (?m)^CHAPTER (?P<title>.*)$\n    \
+                      title same-line\n\naphorism arabic\n    pattern (?m)^[0-9]+\\.[ ]\n    \
+                      suffix \". \"\n";
+        let preset = HeadingPreset::parse(config).unwrap();
+        assert_eq!(preset.primary, Some(HeadingKind::Chapter));
+        assert_eq!(preset.collect_titles("CHAPTER One\n\n1. Text"), vec!["One"]);
+
+        let chapter = preset.rule_for(HeadingKind::Chapter).unwrap();
+        assert_eq!(chapter.render(3, || String::from("Out")).to_text(), "Chapter Three. OUT");
+        let aphorism = preset.rule_for(HeadingKind::Aphorism).unwrap();
+        assert_eq!(aphorism.render(12, String::new).to_text(), "12. ");
+    }
+
+    #[test]
+    fn heading_files_need_a_pattern_for_each_kind() {
+        assert!(HeadingPreset::parse("chapter roman CHAPTER\n").is_err());
+        assert!(HeadingPreset::parse("    pattern CHAPTER\n").is_err());
+        assert!(HeadingPreset::parse("chapter roman\n    title sideways\n").is_err());
+    }
+
+    #[test]
+    fn corpora_without_a_heading_file_have_no_headings() {
+        let preset = load_heading_preset("no/such/corpus.txt", None).unwrap();
+        assert!(preset.rules.is_empty() && preset.primary.is_none());
+        assert!(load_heading_preset("no/such/corpus.txt", Some("no/such.headings")).is_err());
+    }
+
+    #[test]
+    fn roman_numerals_go_on_past_the_thousands() {
+        assert_eq!(roman_numeral(4), "IV");
+        assert_eq!(roman_numeral(49), "XLIX");
+        assert_eq!(roman_numeral(1994), "MCMXCIV");
+        assert_eq!(roman_numeral(4000), "MMMM");
+    }
+
+    #[test]
+    fn numbers_are_spelled_out() {
+        assert_eq!(number_in_words(7), "Seven");
+        assert_eq!(number_in_words(40), "Forty");
+        assert_eq!(number_in_words(23), "Twenty-Three");
+        assert_eq!(number_in_words(105), "One Hundred and Five");
+        assert_eq!(number_in_words(2_000_019), "Two Million and Nineteen");
+        assert_eq!(number_in_words(1_001_100), "One Million One Thousand One Hundred");
+    }
+
+    #[test]
+    fn spelled_out_numbers_follow_the_label_case() {
+        let mut preset = HeadingPreset::parse("chapter words CHAPTER\n    pattern CHAPTER\n")
+                             .unwrap();
+        assert_eq!(preset.rules[0].render(21, String::new).to_text(), "CHAPTER TWENTY-ONE");
+        preset.rules[0].label = String::from("Chapter");
+        assert_eq!(preset.rules[0].render(21, String::new).to_text(), "Chapter Twenty-One");
+    }
+}
@@ -2,318 +2,405 @@
 extern crate rand;
 extern crate num;
 extern crate regex;
+extern crate unicode_normalization;
 
 mod gather_stats;
 mod generate_text;
 mod preprocess;
 mod form_watcher;
+mod form_alphabet;
+mod form_template;
 mod title_generator;
-
+mod search;
+mod gutenberg;
+mod headings;
+mod structure;
+mod pipeline;
+mod verify;
+mod reflow;
+mod decode;
+mod writer;
+mod sentence;
+mod vocabulary;
+mod grammar;
+mod title_model;
+mod chapter_plan;
+mod pen_name;
+mod matter;
+mod series;
+
+use std::cmp;
 use std::env;
 use std::process;
+use std::fs;
 use std::fs::File;
-use std::io::Read;
 use std::io::Write;
-use gather_stats::gather_stats;
-use generate_text::{Args, Generator, pick_random_in_range};
-use title_generator::{generate_title, generate_author};
-use preprocess::{preprocess, extract_form};
-use form_watcher::FormWatcher;
-use regex::Regex;
-use std::collections::HashSet;
-use std::collections::VecDeque;
-
-const INPUT_FILE: &'static str = "input/alice.txt";
-const OUTPUT_FILE: &'static str = "output.txt";
+use gather_stats::{OrderStats, gather_stats};
+use generate_text::{Args, pick_random_in_range, seed_random};
+use title_generator::{TitlePieces, load_title_pieces};
+use preprocess::extract_form;
+use search::{OutputItem, Search};
+use gutenberg::strip_boilerplate;
+use headings::HeadingPreset;
+use structure::{Structure, Token, line_starts};
+use pipeline::{Pipeline, load_pipeline};
+use verify::verify_pipeline;
+use reflow::reflow;
+use decode::{Encoding, read_text_file};
+use writer::{Format, Writer, FORMATS};
+use sentence::{mean_sentence_length, sentence_starts};
+use form_alphabet::FormAlphabet;
+use form_template::FormTemplate;
+use chapter_plan::{ChapterPlan, chapter_lengths};
+use matter::{Epigraph, Matter, colophon, generate_epigraph};
+use series::{BookStats, Series, Volume, manifest_file_name, volume_args};
+
+const INPUT_FILE: &str = "input/alice.txt";
+const OUTPUT_FILE: &str = "output.txt";
 const MIN_ORDER: usize = 3;
 const MAX_ORDER: usize = 6;
 const OUTPUT_CHARS: usize = 142500;
 const MAX_TRIES: usize = 5;
 const DISTORTION_FACTOR: i32 = 10;
 const FORM_MAX_ORDER: usize = 25;
+const BACKTRACK_DEPTH: usize = 4;
+const BACKTRACK_BUDGET: usize = 8;
+const THREADS: usize = 1;
+const REFLOW_WIDTH: usize = 72;
+
+const VOLUME_COUNT: usize = 3;
+
+const INPUT_DIRECTORY: &str = "input";
+
+// What books are generated from, once the corpus is loaded: its pipeline and
+//  title vocabulary, any template, where text may start, and the statistics
+//  of its text and form.
+struct Model<'a> {
+    pipeline: &'a Pipeline,
+    titles: &'a TitlePieces,
+    template: Option<&'a FormTemplate>,
+    starting_keys: &'a [String],
+    text_stats: &'a Vec<OrderStats<'a>>,
+    form_stats: &'a Vec<OrderStats<'a>>,
+}
 
 fn main() {
-    let args = parse_arguments();
-    let raw_text = load_book(&args.input_filename);
+    if env::args().nth(1).is_some_and(|command| command == "verify-preprocess") {
+        verify_preprocess(env::args().skip(2).collect());
+    }
+    let series = env::args().nth(1).is_some_and(|command| command == "series");
+
+    let mut args = parse_arguments();
+    seed_random(args.seed);
+    println!("Seed: {}", args.seed);
+
+    let raw_text = load_corpus(&args.input_filename);
 
     // Preprocess text, to disambuate what characters are content vs. form.
 
-    let processed_text = preprocess(&raw_text);
+    let pipeline = load_or_exit(load_pipeline(&args.input_filename,
+                                              args.heading_filename.as_ref().map(|name| &name[..])));
+    println!("Preprocessing passes: {}.", pipeline.pass_names().join(", "));
+    let mut titles = load_or_exit(load_title_pieces(&args.input_filename, &raw_text));
+    println!("Title vocabulary: {}.", titles.describe());
+    let source_chapter_titles = pipeline.headings.collect_titles(&raw_text);
+    if titles.learn_chapter_titles(&source_chapter_titles) {
+        println!("Chapter titles learned from the source's {} titles.",
+                 source_chapter_titles.len());
+    }
+    titles.count_corpus_words(&raw_text);
+    let processed_text = pipeline.forward(&raw_text);
+    args.sentence_length = mean_sentence_length(&processed_text);
+
+    // A template passage fixes the exact form of the output:
+
+    let template = args.template_filename.as_ref().map(|template_filename| {
+        let template_pipeline = load_or_exit(Pipeline::standard(HeadingPreset::none()));
+        let template_text = template_pipeline.forward(&load_book(template_filename));
+        FormTemplate::new(&template_text, &args.form_alphabet)
+    });
+
+    // Plan chapters after the lengths of the input's (a template has none):
+
+    if let (None, Some(kind)) = (template.as_ref(), pipeline.headings.primary) {
+        let source_lengths = chapter_lengths(&processed_text, kind);
+        args.chapter_plan = ChapterPlan::new(kind,
+                                             &source_lengths,
+                                             args.output_amount,
+                                             args.chapter_count);
+    }
+    if args.chapter_plan.chapter_count() > 0 {
+        println!("Chapter plan: {}.", args.chapter_plan.describe());
+    } else if args.chapter_count.is_some() {
+        println!("The input has no chapters to plan after, so -n is ignored.");
+    }
 
     // Pick a starting point for the text generator:
 
-    // Find "max order" characters that begin a sentence.
-    let mut text_starting_key = String::new();
-    let sentence_ish_starts = Regex::new(r"[A-Z].+").unwrap();
-    let matches = sentence_ish_starts.find_iter(&processed_text).collect::<Vec<_>>();
-    let start_index = pick_random_in_range(0, matches.len() - 1);
-    let start_bounds = matches[start_index];
-    let start_match = &processed_text[start_bounds.0..start_bounds.1];
-    let mut i = 0;
-    for c in start_match.chars() {
-        text_starting_key.push(c);
-        i += 1;
-        if i == args.higher_order_bound {
-            break;
+    // Find "max order" characters that begin a sentence (or, with a template,
+    //  that begin a line and fit the start of the template).
+    let starts: Vec<usize> = if template.is_some() {
+        line_starts(&processed_text)
+    } else {
+        sentence_starts(&processed_text)
+    };
+    let starting_keys = starts.iter()
+                              .map(|&start| {
+                                  processed_text[start..]
+                                      .chars()
+                                      .take(args.higher_order_bound)
+                                      .collect::<String>()
+                              })
+                              .filter(|key| key.chars().count() == args.higher_order_bound)
+                              .filter(|key| {
+                                  template.as_ref().is_none_or(|template| {
+                                      template.fits_start(key, &args.form_alphabet)
+                                  })
+                              })
+                              .collect::<Vec<_>>();
+    if starting_keys.is_empty() {
+        if template.is_some() {
+            println!("No line of the input text fits the start of the template.");
+        } else {
+            println!("No starting point could be found in the input text.");
         }
+        process::exit(1);
     }
+    let start_index = pick_random_in_range(0, starting_keys.len() - 1);
+    let text_starting_key = starting_keys[start_index].clone();
 
     let text = {
         let mut text = String::new();
         text.push_str(&processed_text);
-        text.push_str(" ");
+        text.push(' ');
         text.push_str(&text_starting_key); // Append starting key, so book cannot end.
         text
     };
 
-    let text_form = extract_form(&text);
+    // The form can be learned from a different text than the content, so
+    //  that one text's words are shaped by another's punctuation and rhythm.
+
+    let text_form = match args.form_input_filename {
+        Some(ref form_input_filename) => {
+            let form_pipeline = load_or_exit(load_pipeline(form_input_filename, None));
+            let form_text = form_pipeline.forward(&load_corpus(form_input_filename));
+            extract_form(&form_text, &args.form_alphabet)
+        }
+        None => extract_form(&text, &args.form_alphabet),
+    };
 
     // Gather markov stats about both text and form:
 
     let text_stats = gather_stats(&text, args.higher_order_bound);
     let form_stats = gather_stats(&text_form, FORM_MAX_ORDER);
 
-    // Create a generator for text:
-
-    let mut output = String::new();
-    let mut output_amount = 0;
-    if args.use_html {
-        write_html_header(&mut output, args.lower_order_bound, args.higher_order_bound);
-    }
-
-    output.push_str(&generate_title());
-    output.push_str("\n\n");
-    output.push_str("by ");
-    output.push_str(&generate_author());
-    output.push_str(" \n\n");
-    output.push_str("\u{1F43B}\n\n");
-
-    // Create a form watcher for text:
-    let mut watcher = FormWatcher::new(&form_stats, FORM_MAX_ORDER);
-    let mut worker_watcher = FormWatcher::new(&form_stats, FORM_MAX_ORDER);
-
-    let mut text_generator = Generator::new(&text_stats,
-                                            &args,
-                                            args.lower_order_bound,
-                                            args.higher_order_bound);
-    text_generator.start(Some(&text_starting_key));
-    for c in text_starting_key.chars() {
-        output_char(&mut output, args.use_html, (c, args.higher_order_bound, 25));
-        watcher.watch(c);
-        output_amount += 1;
-    }
-
-    let mut workers = Vec::new();
-    for _ in 0..args.max_tries {
-        let worker = Generator::new(&text_stats,
-                                    &args,
-                                    args.lower_order_bound,
-                                    args.higher_order_bound);
-        let worker_items = VecDeque::new();
-        workers.push((worker, worker_items));
+    let model = Model {
+        pipeline: &pipeline,
+        titles: &titles,
+        template: template.as_ref(),
+        starting_keys: &starting_keys,
+        text_stats: &text_stats,
+        form_stats: &form_stats,
+    };
+    if series {
+        write_series(&model, &args);
+    } else {
+        let title = titles.generate_title();
+        let author = titles.generate_author();
+        let (output, _) = write_book(&model, &args, &title, &author, &text_starting_key);
+        output_file(&args.output_filename, &output);
     }
+}
 
-    const MIN_FORM_COHERENCE: usize = 15;
-
-    'text_generator: loop {
-        let mut chosen = 0;
-        let mut success = false;
-        let mut best = 0;
-        for i in 0..args.max_tries {
-            workers[i].0.sync(&text_generator);
-            worker_watcher.sync(&watcher);
-            workers[i].1.clear();
-
-            let mut next_item = workers[i].0.next();
-            let mut next_watch_item = worker_watcher.watch(next_item.0);
-            workers[i].1.push_back((next_item.0, next_item.1, next_watch_item.0));
-
-            while !next_watch_item.1 {
-                next_item = workers[i].0.next();
-                next_watch_item = worker_watcher.watch(next_item.0);
-                workers[i].1.push_back((next_item.0, next_item.1, next_watch_item.0));
+// Generate a book, under the given title and author, starting with the given
+//  key. Returns the book in the output format, and statistics about it.
+
+fn write_book(model: &Model,
+              args: &Args,
+              title: &str,
+              author: &str,
+              starting_key: &str)
+              -> (String, BookStats) {
+    // The book's front and back matter, with an epigraph from the text's own
+    //  statistics:
+
+    let matter = Matter {
+        dedication: model.titles.expand("dedication"),
+        epigraph: generate_epigraph(model.text_stats, args, model.starting_keys).map(|text| {
+            Epigraph {
+                text,
+                attribution: model.titles.generate_author(),
             }
+        }),
+        blurb: model.titles.expand("blurb"),
+        colophon: colophon(args),
+    };
 
-            let coherence_raised = worker_watcher.current_order > watcher.current_order;
-            let coherence_above_min = worker_watcher.current_order >= MIN_FORM_COHERENCE;
+    // Create a generator for text:
 
-            if coherence_raised || coherence_above_min {
-                chosen = i;
-                success = true;
-                break;
-            } else if best < watcher.current_order {
-                best = i;
-            }
-        }
+    let mut writer = Writer::new(args.format,
+                                 model.pipeline,
+                                 model.titles,
+                                 args.lower_order_bound,
+                                 args.higher_order_bound,
+                                 FORM_MAX_ORDER);
+    writer.begin(title, author);
+    writer.front_matter(&matter);
+    if let (None, Some(kind)) = (model.template, model.pipeline.headings.primary) {
+        writer.structure(Structure::Heading(kind));
+        writer.structure(Structure::Paragraph);
+    }
 
-        if !success {
-            chosen = best;
-            print!("N");
-        } else {
-            print!("Y");
-        }
-        if let Some(item) = workers[chosen].1.iter().next_back() {
-            print!(" {}\n", item.2);
+    // Generate text, searching for candidates which keep the form coherent:
+
+    let mut search = Search::new(model.text_stats,
+                                 model.form_stats,
+                                 args,
+                                 FORM_MAX_ORDER,
+                                 model.template);
+    let items = search.run(starting_key);
+    for item in items.iter() {
+        match item.0 {
+            Token::Text(c) => writer.text(c, item.1, item.2),
+            Token::Structure(structure) => writer.structure(structure),
         }
+    }
 
-        text_generator.sync(&workers[chosen].0);
-        watcher.sync(&worker_watcher);
+    let output2 = writer.finish(&matter);
 
-        for item in workers[chosen].1.iter() {
-            output_char(&mut output, args.use_html, *item);
-            output_amount += 1;
-        }
+    // Only plain text is reflowed; the other formats leave wrapping to
+    //  whatever displays them.
+    let output3 = if args.format != Format::Text || args.reflow_width == 0 {
+        output2
+    } else {
+        reflow(&output2, args.reflow_width)
+    };
 
-        if output_amount >= args.output_amount {
-            let mut last_chars = workers[chosen].1.iter();
-            for _ in 0..5 {
-                if let Some(item) = last_chars.next_back() {
-                    match item.0 {
-                        '.' => break 'text_generator,
-                        '!' => break 'text_generator,
-                        '?' => break 'text_generator,
-                        _ => (),
-                    }
+    (output3, book_stats(model, &items))
+}
+
+// Statistics about a generated book, from the items generated for it.
+
+fn book_stats(model: &Model, items: &[OutputItem]) -> BookStats {
+    let mut stats = BookStats::default();
+    let mut order_total = 0;
+    let mut form_order_total = 0;
+    let mut in_word = false;
+    // The first chapter's heading is written before any text is generated.
+    if let (None, Some(_)) = (model.template, model.pipeline.headings.primary) {
+        stats.chapters = 1;
+    }
+    for item in items.iter() {
+        match item.0 {
+            Token::Text(c) => {
+                stats.characters += 1;
+                order_total += item.1;
+                form_order_total += item.2;
+                if !c.is_whitespace() && !in_word {
+                    stats.words += 1;
+                }
+                in_word = !c.is_whitespace();
+            }
+            Token::Structure(Structure::Heading(kind)) => {
+                if model.pipeline.headings.primary == Some(kind) {
+                    stats.chapters += 1;
                 }
+                in_word = false;
             }
+            Token::Structure(_) => in_word = false,
         }
     }
-
-    let output2 = add_chapter_headings(output);
-    let mut output3 = convert_back_from_preprocess(output2);
-    trim_end(&mut output3);
-
-    output_file(&args.output_filename, &output3);
+    if stats.characters > 0 {
+        stats.mean_order = order_total as f64 / stats.characters as f64;
+        stats.mean_form_order = form_order_total as f64 / stats.characters as f64;
+    }
+    stats
 }
 
-fn add_chapter_headings(output: String) -> String {
-    let mut chapter_number = 0;
-    let roman_numerals = vec!["I", "II", "III", "IV", "V", "VI", "VII", "VIII", "IX", "X", "XI",
-                              "XII", "XIII", "XIV", "XV", "XVI", "XVII", "XVIII", "XIX", "XX"];
-    let bear_face = "\u{1F43B}";
-    let bear_face_pattern = Regex::new(bear_face).unwrap();
-    let bear_faces = bear_face_pattern.find_iter(&output);
-    let mut pair1: (usize, usize) = (0, 0);
-    let mut pair2: (usize, usize) = (0, 0);
-    let mut output2 = String::new();
-    for pair in bear_faces {
-        pair2 = pair;
-
-        output2.push_str(&output[pair1.1..pair2.0]);
-        output2.push_str("CHAPTER ");
-        output2.push_str(roman_numerals[chapter_number]);
-        output2.push_str("\n\n");
-        output2.push_str(&generate_title());
-        chapter_number += 1;
-
-        pair1 = pair2;
+// Generate a series of books from the same model, each written to a file of
+//  its own, and a manifest of them all.
+
+fn write_series(model: &Model, args: &Args) {
+    let mut series = Series::new(model.titles);
+    println!("Series: {}, by {}.", series.title, series.author);
+
+    for number in 1..args.volume_count + 1 {
+        let volume_args = volume_args(args, number, args.volume_count);
+        seed_random(volume_args.seed);
+        let title = series.generate_volume_title(model.titles);
+        let start_index = pick_random_in_range(0, model.starting_keys.len() - 1);
+        let starting_key = &model.starting_keys[start_index];
+        println!("Volume {}: {} (seed {}, orders {} to {}).",
+                 number,
+                 title,
+                 volume_args.seed,
+                 volume_args.lower_order_bound,
+                 volume_args.higher_order_bound);
+
+        let (output, stats) = write_book(model,
+                                         &volume_args,
+                                         &series.full_title(number, &title),
+                                         &series.author,
+                                         starting_key);
+        output_file(&volume_args.output_filename, &output);
+        series.volumes.push(Volume {
+            number,
+            title,
+            file_name: volume_args.output_filename.clone(),
+            seed: volume_args.seed,
+            lower_order_bound: volume_args.lower_order_bound,
+            higher_order_bound: volume_args.higher_order_bound,
+            max_tries: volume_args.max_tries,
+            distortion_factor: volume_args.distortion_factor,
+            stats,
+        });
     }
 
-    output2.push_str(&output[pair1.1..]);
-
-    return output2;
+    let manifest_file_name = manifest_file_name(&args.output_filename);
+    output_file(&manifest_file_name, &series.manifest());
+    println!("Manifest written to {}.", manifest_file_name);
 }
 
-fn convert_back_from_preprocess(output: String) -> String {
-    return output.replace("\u{2018}", "'")
-                 .replace("\u{2019}", "'")
-                 .replace("\u{02BC}", "'")
-                 .replace("\u{2014}", "--");
-}
+// Check that every corpus (or the ones given) survives being preprocessed and
+//  converted back, and exit with the result.
 
-fn trim_end(output: &mut String) {
-    let (i1, i2) = {
-        let mut chars = output.char_indices();
-        let (i2, _) = chars.next_back().unwrap();
-        let (i1, _) = chars.next_back().unwrap();
-        (i1, i2)
+fn verify_preprocess(corpus_file_names: Vec<String>) {
+    let corpus_file_names = if corpus_file_names.is_empty() {
+        list_corpora()
+    } else {
+        corpus_file_names
     };
-    output.remove(i2);
-    output.remove(i1);
-}
 
-fn write_html_header(output_buffer: &mut String, min_order: usize, max_order: usize) {
-    output_buffer.push_str("<meta charset=\"UTF-8\">");
-    output_buffer.push_str("<script type='text/javascript'>
-			window.onload = function () {
-				\
-                            var a = document.getElementById('a');
-				document.body.className = \
-                            'order';
-				var isForm = false;
-				a.onclick = function () {
-					\
-                            isForm = !isForm;
-					document.body.className = isForm ? \
-                            'form-order' : 'order';
-				}
-			};
-		</script>");
-    output_buffer.push_str("<style type=\"text/css\"> body { white-space: pre-wrap; } ");
-    output_buffer.push_str(".structure-success { background: #ddffdd; } ");
-    for i in min_order..max_order + 1 {
-
-        let a = max_order + 1 - min_order;
-        let b = i - min_order;
-        let c = a - b - 1;
-        let multiplier = c as f64 / a as f64;
-
-        let value = (multiplier * 248.0) as i32;
-        output_buffer.push_str("body.order .order-");
-        output_buffer.push_str(&i.to_string());
-        output_buffer.push_str("{ ");
-
-        output_buffer.push_str(" color: rgb(");
-        output_buffer.push_str(&value.to_string());
-        output_buffer.push_str(",");
-        output_buffer.push_str(&value.to_string());
-        output_buffer.push_str(",");
-        output_buffer.push_str(&value.to_string());
-        output_buffer.push_str(");\n");
-
-        output_buffer.push_str("}\n");
-    }
-    for i in 0..FORM_MAX_ORDER + 1 {
-
-        let a = FORM_MAX_ORDER + 1;
-        let b = i;
-        let c = a - b - 1;
-        let multiplier = c as f64 / a as f64;
-
-        let value = (multiplier * 248.0) as i32;
-        output_buffer.push_str("body.form-order .form-order-");
-        output_buffer.push_str(&i.to_string());
-        output_buffer.push_str("{ ");
-
-        output_buffer.push_str(" color: rgb(");
-        output_buffer.push_str(&value.to_string());
-        output_buffer.push_str(",");
-        output_buffer.push_str(&value.to_string());
-        output_buffer.push_str(",");
-        output_buffer.push_str(&value.to_string());
-        output_buffer.push_str(");\n");
-
-        output_buffer.push_str("}\n");
+    let mut failed = Vec::new();
+    for corpus_file_name in corpus_file_names.iter() {
+        let pipeline = load_or_exit(load_pipeline(corpus_file_name, None));
+        let text = load_corpus(corpus_file_name);
+        if !verify_pipeline(corpus_file_name, &text, &pipeline) {
+            failed.push(corpus_file_name.clone());
+        }
     }
-    output_buffer.push_str("</style>");
-    output_buffer.push_str("<input type='checkbox' id='a'> Show Form Stats <br/>");
-}
 
-fn output_char(output_buffer: &mut String, use_html: bool, next_output: (char, usize, usize)) {
-    if use_html {
-        output_buffer.push_str("<span class=\"");
-        output_buffer.push_str("order-");
-        output_buffer.push_str(&next_output.1.to_string());
-        output_buffer.push_str(" form-order-");
-        output_buffer.push_str(&next_output.2.to_string());
-        output_buffer.push_str("\">");
+    if failed.is_empty() {
+        println!("All corpora survived preprocessing, apart from expected losses.");
+        process::exit(0);
+    } else {
+        println!("Unexpected losses in: {}.", failed.join(", "));
+        process::exit(1);
     }
+}
 
-    output_buffer.push(next_output.0);
-
-    if use_html {
-        output_buffer.push_str("</span>");
+fn list_corpora() -> Vec<String> {
+    let mut corpus_file_names = Vec::new();
+    if let Ok(entries) = fs::read_dir(INPUT_DIRECTORY) {
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let path = entry.path();
+            if path.extension().is_some_and(|extension| extension == "txt") {
+                corpus_file_names.push(path.to_string_lossy().into_owned());
+            }
+        }
     }
+    corpus_file_names.sort();
+    corpus_file_names
 }
 
 fn parse_arguments() -> Args {
@@ -321,48 +408,139 @@ fn parse_arguments() -> Args {
     // Initialize args with default values:
     let mut parsed_args = Args {
         input_filename: String::from(INPUT_FILE),
+        form_input_filename: None,
         output_filename: String::from(OUTPUT_FILE),
         lower_order_bound: MIN_ORDER,
         higher_order_bound: MAX_ORDER,
         max_tries: MAX_TRIES,
+        backtrack_depth: BACKTRACK_DEPTH,
+        backtrack_budget: BACKTRACK_BUDGET,
         distortion_factor: DISTORTION_FACTOR,
+        sentence_length: 0,
+        chapter_count: None,
+        volume_count: VOLUME_COUNT,
+        chapter_plan: ChapterPlan::none(),
         output_amount: OUTPUT_CHARS,
-        use_html: false,
+        format: Format::Text,
+        reflow_width: REFLOW_WIDTH,
+        seed: rand::random::<u32>(),
+        threads: THREADS,
+        form_alphabet: FormAlphabet::new(),
+        template_filename: None,
+        heading_filename: None,
     };
 
     for arg in env::args() {
         match &arg[0..2] {
             "-i" => parsed_args.input_filename = String::from(&arg[3..]),
             "-o" => parsed_args.output_filename = String::from(&arg[3..]),
+            "-m" => parsed_args.form_input_filename = Some(String::from(&arg[3..])),
+            "-p" => parsed_args.template_filename = Some(String::from(&arg[3..])),
+            "-c" => parsed_args.heading_filename = Some(String::from(&arg[3..])),
             "-l" => parsed_args.lower_order_bound = parse_usize_or_default(&arg[3..], MIN_ORDER),
             "-h" => parsed_args.higher_order_bound = parse_usize_or_default(&arg[3..], MAX_ORDER),
             "-t" => parsed_args.max_tries = parse_usize_or_default(&arg[3..], MAX_TRIES),
+            "-b" => {
+                parsed_args.backtrack_depth = parse_usize_or_default(&arg[3..], BACKTRACK_DEPTH)
+            }
+            "-B" => {
+                parsed_args.backtrack_budget = parse_usize_or_default(&arg[3..], BACKTRACK_BUDGET)
+            }
             "-d" => {
                 parsed_args.distortion_factor = parse_i32_or_default(&arg[3..], DISTORTION_FACTOR)
             }
             "-a" => parsed_args.output_amount = parse_usize_or_default(&arg[3..], OUTPUT_CHARS),
-            "-f" => parsed_args.use_html = true,
+            "-n" => parsed_args.chapter_count = arg[3..].parse::<usize>().ok(),
+            "-v" => {
+                parsed_args.volume_count = cmp::max(parse_usize_or_default(&arg[3..],
+                                                                           VOLUME_COUNT),
+                                                    1)
+            }
+            "-f" if arg.len() <= 3 => parsed_args.format = Format::Html,
+            "-f" => {
+                match Format::parse(&arg[3..]) {
+                    Some(format) => parsed_args.format = format,
+                    None => {
+                        println!("Unknown format: '{}'. Known formats: {}.",
+                                 &arg[3..],
+                                 FORMATS.join(", "));
+                        process::exit(1);
+                    }
+                }
+            }
+            "-W" => parsed_args.reflow_width = parse_usize_or_default(&arg[3..], REFLOW_WIDTH),
+            "-s" => parsed_args.seed = parse_u32_or_default(&arg[3..], parsed_args.seed),
+            "-j" => parsed_args.threads = parse_usize_or_default(&arg[3..], THREADS),
+            "-w" => {
+                match FormAlphabet::parse(&arg[3..]) {
+                    Ok(alphabet) => parsed_args.form_alphabet = alphabet,
+                    Err(message) => {
+                        println!("{}", message);
+                        process::exit(1);
+                    }
+                }
+            }
             "-?" => print_help(),
             _ => (),
         }
     }
 
-    return parsed_args;
+    // Each word of a template is its own slot to fill.
+    if parsed_args.template_filename.is_some() {
+        parsed_args.form_alphabet.separate_words = true;
+    }
+
+    parsed_args
 }
 
 fn print_help() {
     println!("Arguments: ");
-    println!(" -i: input filename.");
+    println!(" -i: input filename. Titles are made from the words in its title file \
+              (input/alice.titles for input/alice.txt) if it has one, or else from names and \
+              nouns found in it, as are the book's dedication and blurb.");
     println!(" -o: output filename.");
+    println!(" -m: form input filename, to learn the form (punctuation, line breaks and rhythm) \
+              from a different text than the content.");
     println!(" -l: low order bound (minimum order to use).");
     println!(" -h: high order bound (maximum order to use).");
     println!(" -d: distortion factor, how much to distort statistics to achieve structure goals \
               (1-10).");
     println!(" -t: tries, how many times to try generating the desired output to achieve \
               structure goals (1-10).");
+    println!(" -b: backtrack depth, how many committed segments may be rolled back when all \
+              tries fail (0 disables backtracking).");
+    println!(" -B: backtrack budget, how many times to backtrack while repairing a single \
+              stretch of text.");
     println!(" -a: amount of generated output in characters.");
-    println!(" -f: format as html with color coding indicating the order.");
+    println!(" -n: number of chapters to generate. Defaults to as many as the input's chapters \
+              would take to fill the amount of output, and chapters are planned in proportions \
+              drawn from the lengths of the input's.");
+    println!(" -f: output format ({}). Just -f formats as html with color coding indicating \
+              the order.",
+             FORMATS.join(", "));
+    println!(" -W: width to reflow paragraphs of text output to (0 leaves them unwrapped). \
+              Other formats are never reflowed.");
+    println!(" -s: seed for the random number generator, to reproduce a previous run.");
+    println!(" -p: template filename, a passage whose exact form (punctuation, line breaks and \
+              word count) the output should follow.");
+    println!(" -j: number of threads to generate candidate tries on.");
+    println!(" -w: form alphabet, a comma separated list of word length bounds, plus 'caps' to \
+              distinguish capitalization, 'num' to distinguish numerals and 'words' to keep \
+              words separate (e.g. 3,7,caps).");
+    println!(" -c: heading file, saying how to find the headings of the input text and how \
+              to label and number them (in roman numerals, arabic numerals or words), or \
+              'none'. Defaults to the input's own (input/alice.headings for input/alice.txt); \
+              an input without one has no headings.");
+    println!(" -v: number of volumes to generate in series mode.");
     println!(" -?: print help.");
+    println!("Commands: ");
+    println!(" verify-preprocess [files]: check that each corpus (all of input/*.txt by \
+              default) survives preprocessing and converting back.");
+    println!(" series [arguments]: generate a series of books (-v of them) under one series \
+              title and one author, each volume in a file of its own (output-1.txt, \
+              output-2.txt and so on for output.txt) with a seed of its own, and falling back \
+              to lower orders than the one before. A manifest of the volumes' settings and \
+              statistics goes in output.manifest.");
     process::exit(1);
 }
 
@@ -374,6 +552,14 @@ fn parse_usize_or_default(input: &str, default: usize) -> usize {
     }
 }
 
+fn parse_u32_or_default(input: &str, default: u32) -> u32 {
+    if let Ok(arg_u32) = input.parse::<u32>() {
+        arg_u32
+    } else {
+        default
+    }
+}
+
 fn parse_i32_or_default(input: &str, default: i32) -> i32 {
     if let Ok(arg_i32) = input.parse::<i32>() {
         arg_i32
@@ -382,13 +568,38 @@ fn parse_i32_or_default(input: &str, default: i32) -> i32 {
     }
 }
 
+// Load a text file in whatever encoding it's in, or exit with an error if it
+//  can't be read.
+
 fn load_book(file_name: &str) -> String {
-    if let Ok(mut file) = File::open(file_name) {
-        let mut file_contents = String::new();
-        let _ = file.read_to_string(&mut file_contents);
-        file_contents
-    } else {
-        panic!("There was a problem opening the input file.");
+    let (text, encoding) = load_or_exit(read_text_file(file_name));
+    if encoding != Encoding::Utf8 {
+        println!("Read {} as {}.", file_name, encoding);
+    }
+    text
+}
+
+// Load a book that is used for training, stripping any Project Gutenberg
+//  boilerplate from it.
+
+fn load_corpus(file_name: &str) -> String {
+    let (text, removals) = strip_boilerplate(&load_book(file_name));
+    for removal in removals.iter() {
+        println!("Removed {} from {} ({} lines).",
+                 removal.description,
+                 file_name,
+                 removal.lines);
+    }
+    text
+}
+
+fn load_or_exit<T>(result: Result<T, String>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(message) => {
+            println!("{}", message);
+            process::exit(1);
+        }
     }
 }
 
@@ -0,0 +1,195 @@
+use std::cmp;
+use generate_text::{Args, Generator, pick_random_in_range};
+use gather_stats::OrderStats;
+use structure::Token;
+
+// How many passages are generated to choose an epigraph from, and how long
+//  (in words) one may be.
+const EPIGRAPH_CANDIDATES: usize = 30;
+const MIN_EPIGRAPH_WORDS: usize = 6;
+const MAX_EPIGRAPH_WORDS: usize = 30;
+
+// The stream of random numbers epigraphs are generated with, apart from the
+//  ones the search's workers use.
+const EPIGRAPH_STREAM: u32 = 0xe919;
+
+// The parts of a book around its text: a dedication and an epigraph before
+//  it, and a blurb and a colophon after.
+pub struct Matter {
+    pub dedication: Option<String>,
+    pub epigraph: Option<Epigraph>,
+    pub blurb: Option<String>,
+    // Lines saying how the book was made.
+    pub colophon: Vec<String>,
+}
+
+// An epigraph's text is generated, and so still preprocessed: the writer sets
+//  it like the rest of the text, and adds the attribution (and its dash) after.
+pub struct Epigraph {
+    pub text: String,
+    pub attribution: String,
+}
+
+// A colophon listing what the text was generated from and with, so that the
+//  book can be made again.
+
+pub fn colophon(args: &Args) -> Vec<String> {
+    let mut lines = vec![format!("Generated from {}.", args.input_filename)];
+    if let Some(ref form_input_filename) = args.form_input_filename {
+        lines.push(format!("Form learned from {}.", form_input_filename));
+    }
+    lines.push(format!("Orders {} to {}, {} tries per segment, distortion factor {}.",
+                       args.lower_order_bound,
+                       args.higher_order_bound,
+                       args.max_tries,
+                       args.distortion_factor));
+    lines.push(format!("Seed {}.", args.seed));
+    lines
+}
+
+// Generate a short passage for an epigraph: the most likely of a number of
+//  single sentences generated at the highest order from the given starting
+//  keys, by the mean log probability of their characters. Returns None if no passage of a
+//  suitable length comes out.
+
+pub fn generate_epigraph(stats: &Vec<OrderStats>,
+                         args: &Args,
+                         starting_keys: &[String])
+                         -> Option<String> {
+    if starting_keys.is_empty() {
+        return None;
+    }
+
+    let mut generator = Generator::new(stats,
+                                       args,
+                                       args.higher_order_bound,
+                                       args.higher_order_bound);
+    generator.set_stream(args.seed, EPIGRAPH_STREAM);
+
+    let mut best: Option<(f64, String)> = None;
+    for _ in 0..EPIGRAPH_CANDIDATES {
+        let starting_key = &starting_keys[pick_random_in_range(0, starting_keys.len() - 1)];
+        let passage = match generate_sentence(&mut generator, starting_key) {
+            Some(passage) => passage,
+            None => continue,
+        };
+        let words = passage.split_whitespace().count();
+        if !(MIN_EPIGRAPH_WORDS..=MAX_EPIGRAPH_WORDS).contains(&words) ||
+           !is_balanced(&passage) {
+            continue;
+        }
+
+        let score = likelihood(&passage, stats);
+        if best.as_ref().is_none_or(|&(best_score, _)| score > best_score) {
+            best = Some((score, passage));
+        }
+    }
+
+    best.map(|(_, passage)| passage)
+}
+
+// Generate a single sentence, or None if it runs into a piece of structure
+//  (like a paragraph break) or on for too long.
+
+fn generate_sentence(generator: &mut Generator, starting_key: &str) -> Option<String> {
+    generator.start(Some(starting_key));
+    let mut sentence = String::from(starting_key);
+    let mut words = 0;
+    loop {
+        match generator.next().0 {
+            Token::Text(c) => {
+                if c.is_whitespace() {
+                    words += 1;
+                    if words > MAX_EPIGRAPH_WORDS {
+                        return None;
+                    }
+                }
+                sentence.push(c);
+            }
+            Token::Structure(_) => return None,
+        }
+        if generator.ends_sentence() {
+            return Some(String::from(sentence.trim()));
+        }
+    }
+}
+
+// Whether every quote and bracket in a passage is closed within it, and none
+//  is closed that wasn't opened in it (as when the passage starts
+//  mid-quote). Straight double quotes, in a corpus whose quotes aren't
+//  paired, only have to come in pairs.
+
+fn is_balanced(passage: &str) -> bool {
+    let mut open = Vec::new();
+    let mut straight_quotes = 0;
+    for c in passage.chars() {
+        match c {
+            '\u{201C}' | '\u{2018}' | '(' | '[' => open.push(c),
+            '\u{201D}' if open.pop() != Some('\u{201C}') => return false,
+            '\u{2019}' if open.pop() != Some('\u{2018}') => return false,
+            ')' if open.pop() != Some('(') => return false,
+            ']' if open.pop() != Some('[') => return false,
+            '"' => straight_quotes += 1,
+            _ => {}
+        }
+    }
+    open.is_empty() && straight_quotes % 2 == 0
+}
+
+// The mean log probability of each character of a passage following the
+//  ones before it, at the highest order the statistics have a state for.
+
+fn likelihood(passage: &str, stats: &[OrderStats]) -> f64 {
+    let chars = passage.chars().collect::<Vec<_>>();
+    let mut total = 0.0;
+    let mut count = 0;
+    for i in 1..chars.len() {
+        for order in (1..cmp::min(stats.len(), i) + 1).rev() {
+            let key = chars[i - order..i].iter().cloned().collect::<String>();
+            if let Some(choice_stats) = stats[order - 1].stats_for_state.get(&key[..]) {
+                let next_count = choice_stats.options.get(&chars[i]).cloned().unwrap_or(0);
+                if next_count > 0 {
+                    total += (next_count as f64 / choice_stats.total_usages as f64).ln();
+                    count += 1;
+                    break;
+                }
+            }
+        }
+    }
+    if count == 0 {
+        return f64::NEG_INFINITY;
+    }
+    total / count as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use generate_text::test_args;
+
+    #[test]
+    fn colophons_say_how_to_make_the_book_again() {
+        let mut args = test_args();
+        assert_eq!(colophon(&args),
+                   vec!["Generated from test.txt.",
+                        "Orders 1 to 4, 1 tries per segment, distortion factor 0.",
+                        "Seed 1."]);
+
+        args.form_input_filename = Some(String::from("form.txt"));
+        assert_eq!(colophon(&args)[1], "Form learned from form.txt.");
+    }
+
+    #[test]
+    fn epigraphs_need_balanced_quotes_and_brackets() {
+        assert!(is_balanced("\u{201C}Off with her head!\u{201D} said the Queen."));
+        assert!(is_balanced("\u{2018}It\u{02BC}s \u{201C}mine\u{201D},\u{2019} he said."));
+        assert!(is_balanced("She said \"yes\" twice."));
+        assert!(is_balanced("She hoped (in vain) for a fan [sic]."));
+        assert!(!is_balanced("said the Queen.\u{201D}"));
+        assert!(!is_balanced("\u{201C}Off with her head!"));
+        assert!(!is_balanced("\u{2018}Oh, \u{201C}no,\u{2019} he said."));
+        assert!(!is_balanced("She said \"yes."));
+        assert!(!is_balanced("hoped) a fan!"));
+        assert!(!is_balanced("(a fan, \u{201C}she said)\u{201D}"));
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::{BTreeMap, BTreeSet};
+use generate_text::pick_random_in_range;
+use grammar::capitalize;
+
+// Common English first names and surnames, which every name model learns
+//  from along with the names of the corpus, so that pen names sound like
+//  names even when the corpus has few.
+const FIRST_NAMES: [&str; 48] = ["Agnes", "Albert", "Alfred", "Amelia", "Anne",
+                                 "Arthur", "Beatrice", "Charles", "Charlotte", "Clara",
+                                 "Daniel", "Edith", "Edmund", "Edward", "Eleanor",
+                                 "Eliza", "Emily", "Florence", "Frances", "Frederick",
+                                 "George", "Harriet", "Henry", "Herbert", "Isabel",
+                                 "James", "Jane", "John", "Joseph", "Julia", "Louisa",
+                                 "Lucy", "Margaret", "Maria", "Martha", "Mary",
+                                 "Matilda", "Nathaniel", "Oliver", "Rosamund", "Samuel",
+                                 "Sarah", "Thomas", "Violet", "Walter", "Wilfred",
+                                 "Winifred", "Thaddeus"];
+const SURNAMES: [&str; 48] = ["Ashdown", "Barrow", "Blackwood", "Brampton", "Carver",
+                              "Chandler", "Cresswell", "Dalton", "Darnell", "Edgeworth",
+                              "Fairfax", "Fenwick", "Fletcher", "Gaskell", "Hardcastle",
+                              "Harding", "Hawthorne", "Holloway", "Kingsley", "Langley",
+                              "Lockwood", "Marlowe", "Merriman", "Middleton", "Norcross",
+                              "Oakley", "Pemberton", "Prescott", "Radcliffe", "Rowland",
+                              "Sedgwick", "Shelley", "Somerville", "Stanhope", "Thackeray",
+                              "Thornbury", "Trollope", "Underwood", "Wainwright",
+                              "Wentworth", "Whitcombe", "Winslow", "Woodhouse", "Yardley",
+                              "Bramwell", "Ellison", "Featherstone", "Grantham"];
+
+// The number of letters each letter of a name is chosen by.
+const ORDER: usize = 2;
+
+// Generated names outside these lengths are thrown away, as are names the
+//  model was taught (or any other real name). After so many tries, a name
+//  shorter than the minimum will do.
+const MIN_LENGTH: usize = 4;
+const MAX_LENGTH: usize = 11;
+const MAX_TRIES: usize = 50;
+
+// The characters that stand for the start and end of a name.
+const START: char = '^';
+const END: char = '$';
+
+// A character level Markov model of names, which makes up new ones that sound
+//  like them.
+struct NameModel {
+    next_letters: BTreeMap<String, Vec<char>>,
+}
+
+impl NameModel {
+    // Learn from names of a single word of letters (which leaves out the
+    //  likes of "Frog-Footman").
+
+    fn train<'a, I: Iterator<Item = &'a str>>(names: I) -> NameModel {
+        let mut model = NameModel { next_letters: BTreeMap::new() };
+        for name in names.filter(|name| name.chars().all(|c| c.is_alphabetic())) {
+            let mut key = START.to_string().repeat(ORDER);
+            for c in name.to_lowercase().chars().chain(Some(END)) {
+                model.next_letters.entry(key.clone()).or_default().push(c);
+                key.remove(0);
+                key.push(c);
+            }
+        }
+        model
+    }
+
+    // Make up a name, or None if it runs on too long.
+
+    fn generate(&self) -> Option<String> {
+        let mut name = String::new();
+        let mut key = START.to_string().repeat(ORDER);
+        loop {
+            let letters = self.next_letters.get(&key)?;
+            let c = letters[pick_random_in_range(0, letters.len() - 1)];
+            if c == END {
+                return Some(capitalize(&name));
+            }
+            if name.chars().count() == MAX_LENGTH {
+                return None;
+            }
+            name.push(c);
+            key.remove(0);
+            key.push(c);
+        }
+    }
+}
+
+// Makes up authors' names: a first name and a surname, each from a model of
+//  names of its kind, sometimes with the first name (or a middle name) cut
+//  down to an initial, like "E. M. Forster".
+pub struct PenNames {
+    first_names: NameModel,
+    surnames: NameModel,
+    // Names that must not be given: the ones the models were taught, and any
+    //  other names of the corpus, in lower case.
+    real_names: BTreeSet<String>,
+}
+
+impl PenNames {
+    // Learn from the bundled names and the given names of the corpus. The
+    //  corpus's names are learned as both first names and surnames.
+
+    pub fn new(corpus_names: &[String]) -> PenNames {
+        let corpus_names_iter = || corpus_names.iter().map(|name| &name[..]);
+        let first_names = FIRST_NAMES.iter().cloned().chain(corpus_names_iter());
+        let surnames = SURNAMES.iter().cloned().chain(corpus_names_iter());
+
+        let mut real_names = BTreeSet::new();
+        for name in FIRST_NAMES.iter().chain(SURNAMES.iter()) {
+            real_names.insert(name.to_lowercase());
+        }
+        for name in corpus_names.iter() {
+            real_names.insert(name.to_lowercase());
+        }
+
+        PenNames {
+            first_names: NameModel::train(first_names),
+            surnames: NameModel::train(surnames),
+            real_names,
+        }
+    }
+
+    // Rule out more real names: all of the corpus's, say.
+
+    pub fn exclude<'a, I: Iterator<Item = &'a str>>(&mut self, names: I) {
+        self.real_names.extend(names.map(|name| name.to_lowercase()));
+    }
+
+    pub fn generate(&self) -> String {
+        let first_name = self.generate_name(&self.first_names);
+        let surname = self.generate_name(&self.surnames);
+        let initial = |name: &str| format!("{}.", name.chars().next().unwrap_or('A'));
+
+        match pick_random_in_range(0, 9) {
+            0 | 1 => format!("{} {}", initial(&first_name), surname),
+            2 | 3 => {
+                let middle_name = self.generate_name(&self.first_names);
+                format!("{} {} {}", initial(&first_name), initial(&middle_name), surname)
+            }
+            4 | 5 => {
+                let middle_name = self.generate_name(&self.first_names);
+                format!("{} {} {}", first_name, initial(&middle_name), surname)
+            }
+            _ => format!("{} {}", first_name, surname),
+        }
+    }
+
+    // A made up name which isn't a real one. Every model learns from the
+    //  bundled names, which mix well enough that one always comes out.
+
+    fn generate_name(&self, model: &NameModel) -> String {
+        let mut tries = 0;
+        loop {
+            tries += 1;
+            if let Some(name) = model.generate() {
+                let long_enough = name.chars().count() >= MIN_LENGTH || tries > MAX_TRIES;
+                if long_enough && !self.real_names.contains(&name.to_lowercase()) {
+                    return name;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use generate_text::seed_random;
+
+    #[test]
+    fn made_up_names_are_not_real_ones() {
+        let corpus_names = vec![String::from("Alice"), String::from("Dinah")];
+        let mut pen_names = PenNames::new(&corpus_names);
+        pen_names.exclude(["Hatter", "Gryphon"].iter().cloned());
+
+        seed_random(1);
+        for _ in 0..20 {
+            let name = pen_names.generate_name(&pen_names.surnames);
+            let length = name.chars().count();
+            assert!(length <= MAX_LENGTH, "{}", name);
+            assert!(!pen_names.real_names.contains(&name.to_lowercase()), "{}", name);
+        }
+        assert!(pen_names.real_names.contains("hatter"));
+        assert!(pen_names.real_names.contains("alice"));
+    }
+
+    #[test]
+    fn pen_names_are_capitalized_with_initials() {
+        let pen_names = PenNames::new(&[]);
+
+        seed_random(1);
+        for _ in 0..20 {
+            let name = pen_names.generate();
+            let parts: Vec<&str> = name.split(' ').collect();
+            assert!(parts.len() == 2 || parts.len() == 3, "{}", name);
+            for part in parts.iter() {
+                assert!(part.chars().next().unwrap().is_uppercase(), "{}", name);
+                let is_initial = part.chars().count() == 2 && part.ends_with('.');
+                assert!(is_initial || part.chars().all(|c| c.is_alphabetic()), "{}", name);
+            }
+            assert!(!parts.last().unwrap().ends_with('.'), "{}", name);
+        }
+    }
+}
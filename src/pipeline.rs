@@ -0,0 +1,158 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use headings::{HeadingPreset, load_heading_preset};
+use preprocess::create_pass;
+
+// One step of preprocessing. The forward direction disambiguates the text
+//  for the model (curly quotes, structure tokens, ...), and the inverse turns
+//  generated text back into plain text.
+//
+// A pass is lossy if its inverse isn't expected to give back the original
+//  text (chapter titles are replaced by a token, for instance). A lossy pass
+//  says exactly what it may change: whether the lines removed from a text by
+//  a round trip through it, and the lines added in their place (each joined
+//  by newlines), are a change it accounts for.
+
+pub trait Pass {
+    fn name(&self) -> &'static str;
+    fn forward(&self, text: &str) -> String;
+    fn inverse(&self, text: &str) -> String;
+
+    fn may_change(&self, _removed: &str, _added: &str) -> bool {
+        false
+    }
+}
+
+// The passes a corpus is preprocessed with, in order. The inverse runs the
+//  passes' inverses in reverse order, so it always mirrors the forward
+//  direction.
+pub struct Pipeline {
+    pub passes: Vec<Box<dyn Pass>>,
+    // The preset headings are detected with, for rendering them in output.
+    pub headings: HeadingPreset,
+}
+
+// The passes used for a corpus without a pipeline file.
+const STANDARD_PASSES: [&str; 10] = ["reserved",
+                                     "markup",
+                                     "contractions",
+                                     "single-quotes",
+                                     "apostrophes",
+                                     "double-quotes",
+                                     "dashes",
+                                     "headings",
+                                     "unwrap",
+                                     "breaks"];
+
+impl Pipeline {
+    // The standard passes, with headings found by the given preset.
+
+    pub fn standard(headings: HeadingPreset) -> Result<Pipeline, String> {
+        Pipeline::parse(&STANDARD_PASSES.join("\n"), headings)
+    }
+
+    // Parse a pipeline file: one pass per line, by name. Blank lines and
+    //  lines starting with '#' are ignored. The headings pass finds headings
+    //  by the given preset.
+
+    pub fn parse(config: &str, headings: HeadingPreset) -> Result<Pipeline, String> {
+        let mut passes = Vec::new();
+        for (line_number, line) in config.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut words = line.split_whitespace();
+            let name = words.next().unwrap();
+            if words.next().is_some() {
+                return Err(format!("Line {}: pass '{}' takes no argument.", line_number + 1, name));
+            }
+
+            let pass = create_pass(name, &headings).map_err(|message| {
+                format!("Line {}: {}", line_number + 1, message)
+            })?;
+            passes.push(pass);
+        }
+
+        Ok(Pipeline { passes, headings })
+    }
+
+    pub fn pass_names(&self) -> Vec<&'static str> {
+        self.passes.iter().map(|pass| pass.name()).collect()
+    }
+
+    pub fn forward(&self, text: &str) -> String {
+        let mut output = String::from(text);
+        for pass in self.passes.iter() {
+            output = pass.forward(&output);
+        }
+        output
+    }
+
+    pub fn inverse(&self, text: &str) -> String {
+        let mut output = String::from(text);
+        for pass in self.passes.iter().rev() {
+            output = pass.inverse(&output);
+        }
+        output
+    }
+}
+
+// The pipeline file for a corpus sits next to it, with the extension
+//  ".passes".
+
+pub fn pipeline_file_for(corpus_file_name: &str) -> String {
+    let path = Path::new(corpus_file_name).with_extension("passes");
+    path.to_string_lossy().into_owned()
+}
+
+// Load the pipeline for a corpus from its pipeline file, or use the standard
+//  passes if it has none. Headings are found by the corpus's heading file,
+//  or by the given one instead.
+
+pub fn load_pipeline(corpus_file_name: &str,
+                     heading_file_name: Option<&str>)
+                     -> Result<Pipeline, String> {
+    let headings = load_heading_preset(corpus_file_name, heading_file_name)?;
+    let pipeline_file_name = pipeline_file_for(corpus_file_name);
+
+    let config = match File::open(&pipeline_file_name) {
+        Ok(mut file) => {
+            let mut config = String::new();
+            file.read_to_string(&mut config)
+                .map_err(|error| format!("Could not read {}: {}", pipeline_file_name, error))?;
+            config
+        }
+        Err(_) => return Pipeline::standard(headings),
+    };
+
+    Pipeline::parse(&config, headings).map_err(|message| {
+        format!("Invalid pipeline file {}: {}", pipeline_file_name, message)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pipeline_files_name_passes_in_order() {
+        let config = "# Quotes first.\ndouble-quotes\n\n  dashes\n";
+        let pipeline = Pipeline::parse(config, HeadingPreset::none()).unwrap();
+        assert_eq!(pipeline.pass_names(), vec!["double-quotes", "dashes"]);
+
+        let text = "\"Wait--\" she said.";
+        let processed = pipeline.forward(text);
+        assert_eq!(processed, "\u{201C}Wait\u{2014}\u{201D} she said.");
+        assert_eq!(pipeline.inverse(&processed), text);
+    }
+
+    #[test]
+    fn unknown_passes_and_arguments_are_errors() {
+        let unknown = Pipeline::parse("reserved\nsparkle\n", HeadingPreset::none());
+        assert_eq!(unknown.err(), Some(String::from("Line 2: Unknown pass: 'sparkle'.")));
+        assert!(Pipeline::parse("headings alice\n", HeadingPreset::none()).is_err());
+    }
+}
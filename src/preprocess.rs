@@ -1,58 +1,343 @@
 
+use regex;
 use regex::Regex;
 use std::collections::VecDeque;
+use form_alphabet::{FormAlphabet, FormTokenizer};
+use headings::{HeadingPreset, HeadingRenderer};
+use structure::{Structure, strip_reserved, mark_breaks, mark_scene_breaks};
+use title_generator::TitlePieces;
+use pipeline::Pass;
+use reflow::unwrap;
 
-pub fn preprocess(input: &String) -> String {
-    let contraction_pattern = Regex::new(r"(\w)'(\w)").unwrap();
-    let leftover_pattern = Regex::new(r"'").unwrap();
-    let dash_pattern = Regex::new(r"--").unwrap();
-    let chapter_title_pattern = Regex::new(r"CHAPTER .*\n\n.*").unwrap();
+// The passes a pipeline can be built from. The headings pass finds headings
+//  by the given preset.
 
-    let text1 = input;
-    let text2 = contraction_pattern.replace_all(&text1, "$1\u{02BC}$2");
-    let text3 = process_quotes(&text2);
-    let text4 = leftover_pattern.replace_all(&text3, "\u{02BC}");
-    let text5 = dash_pattern.replace_all(&text4, "\u{2014}");
-    let text6 = chapter_title_pattern.replace_all(&text5, "\u{1F43B}"); // bear face
+pub fn create_pass(name: &str, headings: &HeadingPreset) -> Result<Box<dyn Pass>, String> {
+    let pass: Box<dyn Pass> = match name {
+        "headings" => Box::new(HeadingsPass { preset: headings.clone() }),
+        "reserved" => Box::new(ReservedPass),
+        "markup" => Box::new(MarkupPass),
+        "contractions" => Box::new(ContractionsPass),
+        "single-quotes" => Box::new(QuotesPass::single()),
+        "apostrophes" => Box::new(ApostrophesPass),
+        "double-quotes" => Box::new(QuotesPass::double()),
+        "dashes" => Box::new(DashesPass),
+        "unwrap" => Box::new(UnwrapPass),
+        "breaks" => Box::new(BreaksPass),
+        _ => return Err(format!("Unknown pass: '{}'.", name)),
+    };
+    Ok(pass)
+}
+
+// Remove characters which are reserved for structure tokens.
+struct ReservedPass;
+
+impl Pass for ReservedPass {
+    fn name(&self) -> &'static str {
+        "reserved"
+    }
 
-    return text6;
+    fn forward(&self, text: &str) -> String {
+        strip_reserved(text)
+    }
+
+    fn inverse(&self, text: &str) -> String {
+        String::from(text)
+    }
 }
 
-fn process_quotes(input: &String) -> String {
-    let quotes_pattern = Regex::new(r"([^\w])'((?s).*?)'([^\w])").unwrap();
-    let mut open_quotes = VecDeque::new();
-    let mut close_quotes = VecDeque::new();
-    let mut search_text = &input[..];
-    let mut search_text_offset = 0;
+// Editorial markup: illustrations, footnotes and footnote markers are
+//  removed, and _underscored_ text becomes an emphasis span. (Scene breaks
+//  are left to the breaks pass.)
+struct MarkupPass;
 
-    // Locate opening and closing quotes:
+impl Pass for MarkupPass {
+    fn name(&self) -> &'static str {
+        "markup"
+    }
+
+    fn forward(&self, text: &str) -> String {
+        let emphasis_pattern = Regex::new(r"_((?:[^_\n]|\n[^_\n])+)_").unwrap();
+        let emphasis = format!("{}$1{}",
+                               Structure::EmphasisStart.token(),
+                               Structure::EmphasisEnd.token());
+        emphasis_pattern.replace_all(&strip_markup(text), &emphasis[..])
+    }
+
+    fn inverse(&self, text: &str) -> String {
+        text.replace([Structure::EmphasisStart.token(), Structure::EmphasisEnd.token()], "_")
+    }
 
-    while let Some((a, b)) = quotes_pattern.find(search_text) {
+    // Only the markup may go, along with the lines it was alone on.
 
-        // Create an iterator over char indices of match.
-        let slice = &input[a..b];
-        let mut slice_chars = slice.char_indices();
+    fn may_change(&self, removed: &str, added: &str) -> bool {
+        non_blank_lines(&strip_markup(removed)) == non_blank_lines(added)
+    }
+}
+
+fn strip_markup(text: &str) -> String {
+    let illustration_line_pattern = Regex::new(r"(?m)^[ \t]*\[Illustration[^\]]*\][ \t]*\n")
+                                        .unwrap();
+    let illustration_pattern = Regex::new(r"\[Illustration[^\]]*\]").unwrap();
+    let footnote_pattern = Regex::new(r" ?\[Footnote[^\]]*\]").unwrap();
+    let marker_pattern = Regex::new(r" ?\[([0-9]+|[A-Z])\]").unwrap();
+
+    let text1 = illustration_line_pattern.replace_all(text, "");
+    let text2 = illustration_pattern.replace_all(&text1, "");
+    let text3 = footnote_pattern.replace_all(&text2, "");
+    marker_pattern.replace_all(&text3, "")
+}
+
+fn non_blank_lines(text: &str) -> Vec<&str> {
+    text.lines().filter(|line| !line.trim().is_empty()).collect()
+}
+
+// Apostrophes inside words (don't, Alice's) become modifier letter
+//  apostrophes, so they aren't mistaken for quotes.
+struct ContractionsPass;
 
-        // Mark the opening quote apostrophe:
-        let _ = slice_chars.next().unwrap();
-        let (open_quote, _) = slice_chars.next().unwrap();
-        open_quotes.push_front(search_text_offset + a + open_quote);
+impl Pass for ContractionsPass {
+    fn name(&self) -> &'static str {
+        "contractions"
+    }
+
+    fn forward(&self, text: &str) -> String {
+        let contraction_pattern = Regex::new(r"(\w)'(\w)").unwrap();
+        contraction_pattern.replace_all(text, "$1\u{02BC}$2")
+    }
+
+    fn inverse(&self, text: &str) -> String {
+        text.replace("\u{02BC}", "'")
+    }
+}
 
-        // Find the closing quote apostrophe:
-        let close_quote = {
-            let mut offset2 = open_quote;
-            let (mut offset1, _) = slice_chars.next().unwrap();
+// Any straight single quotes that are left over after pairing are
+//  apostrophes too (the dogs' tails, 'tis).
+struct ApostrophesPass;
+
+impl Pass for ApostrophesPass {
+    fn name(&self) -> &'static str {
+        "apostrophes"
+    }
+
+    fn forward(&self, text: &str) -> String {
+        text.replace("'", "\u{02BC}")
+    }
+
+    fn inverse(&self, text: &str) -> String {
+        text.replace("\u{02BC}", "'")
+    }
+}
+
+struct QuotesPass {
+    name: &'static str,
+    quote: char,
+    open: char,
+    close: char,
+}
+
+impl QuotesPass {
+    fn single() -> QuotesPass {
+        QuotesPass {
+            name: "single-quotes",
+            quote: '\'',
+            open: '\u{2018}',
+            close: '\u{2019}',
+        }
+    }
+
+    fn double() -> QuotesPass {
+        QuotesPass {
+            name: "double-quotes",
+            quote: '"',
+            open: '\u{201C}',
+            close: '\u{201D}',
+        }
+    }
+}
+
+impl Pass for QuotesPass {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn forward(&self, text: &str) -> String {
+        process_quotes(text, self.quote, self.open, self.close)
+    }
+
+    fn inverse(&self, text: &str) -> String {
+        let quote = self.quote.to_string();
+        text.replace([self.open, self.close], &quote)
+    }
+}
+
+struct DashesPass;
+
+impl Pass for DashesPass {
+    fn name(&self) -> &'static str {
+        "dashes"
+    }
+
+    fn forward(&self, text: &str) -> String {
+        text.replace("--", "\u{2014}")
+    }
+
+    fn inverse(&self, text: &str) -> String {
+        text.replace("\u{2014}", "--")
+    }
+}
+
+// Headings become structure tokens, and are rendered anew (numbered in
+//  order, with generated titles) on the way back.
+struct HeadingsPass {
+    preset: HeadingPreset,
+}
 
-            while let Some((offset, _)) = slice_chars.next() {
-                offset2 = offset1;
-                offset1 = offset;
+impl Pass for HeadingsPass {
+    fn name(&self) -> &'static str {
+        "headings"
+    }
+
+    fn forward(&self, text: &str) -> String {
+        self.preset.mark_headings(text)
+    }
+
+    // Headings may only lose their titles (and the spacing around their
+    //  numbers): the text around them, and their labels and numbers, come
+    //  back.
+
+    fn may_change(&self, removed: &str, added: &str) -> bool {
+        self.preset.mark_headings(removed) == self.preset.mark_headings(added) &&
+        self.preset.heading_labels(removed) == self.preset.heading_labels(added)
+    }
+
+    fn inverse(&self, text: &str) -> String {
+        let is_heading = |c| matches!(Structure::from_token(c), Some(Structure::Heading(_)));
+        if !text.chars().any(is_heading) {
+            return String::from(text);
+        }
+
+        // The titles are lost anyway, so any will do.
+        let titles = TitlePieces::untitled();
+        let mut renderer = HeadingRenderer::new(&self.preset, &titles);
+        let mut output = String::new();
+        for c in text.chars() {
+            match Structure::from_token(c) {
+                Some(Structure::Heading(kind)) => {
+                    if let Some(heading) = renderer.render(kind) {
+                        output.push_str(&heading.to_text());
+                    }
+                }
+                _ => output.push(c),
+            }
+        }
+        output
+    }
+}
+
+// Hard-wrapped paragraphs are joined into single lines. Where the lines were
+//  broken is lost; generated text is reflowed when it is written instead.
+struct UnwrapPass;
+
+impl Pass for UnwrapPass {
+    fn name(&self) -> &'static str {
+        "unwrap"
+    }
+
+    // Lines may only be joined: each line that comes back is a run of the
+    //  lines that went, joined by spaces.
+
+    fn may_change(&self, removed: &str, added: &str) -> bool {
+        let mut removed_lines = removed.lines();
+        for line in added.lines() {
+            let line = line.trim_end();
+            let mut joined = match removed_lines.next() {
+                Some(first) => String::from(first.trim_end()),
+                None => return false,
+            };
+            while joined != line {
+                match removed_lines.next() {
+                    Some(next) if !next.trim().is_empty() && joined.len() < line.len() => {
+                        joined.push(' ');
+                        joined.push_str(next.trim());
+                    }
+                    _ => return false,
+                }
+            }
+        }
+        removed_lines.next().is_none()
+    }
+
+    fn forward(&self, text: &str) -> String {
+        unwrap(text)
+    }
+
+    fn inverse(&self, text: &str) -> String {
+        String::from(text)
+    }
+}
+
+// Blank lines between blocks and scene breaks become structure tokens. How
+//  many blank lines there were, and how a scene break was drawn, is lost.
+struct BreaksPass;
+
+impl Pass for BreaksPass {
+    fn name(&self) -> &'static str {
+        "breaks"
+    }
+
+    // Only blank lines, and how scene breaks are drawn, may change.
+
+    fn may_change(&self, removed: &str, added: &str) -> bool {
+        let scene_break = Structure::SceneBreak.token().to_string();
+        let removed = mark_scene_breaks(removed).replace(&scene_break[..], "* * *");
+        non_blank_lines(&removed) == non_blank_lines(added)
+    }
+
+    fn forward(&self, text: &str) -> String {
+        mark_breaks(text)
+    }
+
+    fn inverse(&self, text: &str) -> String {
+        let mut output = String::new();
+        for c in text.chars() {
+            match Structure::from_token(c) {
+                Some(Structure::SceneBreak) => output.push_str("* * *"),
+                Some(Structure::Paragraph) | Some(Structure::Stanza) => output.push_str("\n\n"),
+                _ => output.push(c),
             }
-            offset2
-        };
-        close_quotes.push_front(search_text_offset + a + close_quote);
+        }
+        output
+    }
+}
+
+// Pair up straight quotes into opening and closing quotes. A quote only opens
+//  after a non-word character and only closes before one, so apostrophes
+//  inside words are left alone.
+
+fn process_quotes(input: &str, quote: char, open: char, close: char) -> String {
+    let quote_pattern = regex::quote(&quote.to_string());
+    let quotes_pattern = Regex::new(&format!(r"(^|[^\w]){0}((?s).*?){0}([^\w]|$)", quote_pattern))
+                             .unwrap();
+    let mut open_quotes = VecDeque::new();
+    let mut close_quotes = VecDeque::new();
+    let mut search_text = input;
+    let mut search_text_offset = 0;
+
+    // Locate opening and closing quotes:
+
+    while let Some(captures) = quotes_pattern.captures(search_text) {
+
+        // The opening quote comes right before the quoted text, and the
+        //  closing quote right after it.
+        let (quoted_start, quoted_end) = captures.pos(2).unwrap();
+        let open_quote = quoted_start - quote.len_utf8();
+        let close_quote = quoted_end;
+        open_quotes.push_front(search_text_offset + open_quote);
+        close_quotes.push_front(search_text_offset + close_quote);
 
         // Move search text window:
-        search_text_offset = search_text_offset + a + close_quote + 1;
+        search_text_offset = search_text_offset + close_quote + quote.len_utf8();
         search_text = &input[search_text_offset..];
     }
 
@@ -63,38 +348,79 @@ fn process_quotes(input: &String) -> String {
     let mut close_quote = close_quotes.pop_back();
     for (i, c) in input.char_indices() {
         if open_quote.is_some() && open_quote.unwrap() == i {
-            output.push('\u{2018}');
+            output.push(open);
             open_quote = open_quotes.pop_back();
         } else if close_quote.is_some() && close_quote.unwrap() == i {
-            output.push('\u{2019}');
+            output.push(close);
             close_quote = close_quotes.pop_back();
         } else {
             output.push(c);
         }
     }
 
-    return output;
+    output
 }
 
-pub fn extract_form(processed_text: &String) -> String {
+pub fn extract_form(processed_text: &str, alphabet: &FormAlphabet) -> String {
     let mut output = String::new();
-
-    let mut saw_alphabetic = false;
+    let mut tokenizer = FormTokenizer::new(alphabet);
 
     for c in processed_text.chars() {
-        if c.is_alphabetic() || c == '-' {
-            saw_alphabetic = true;
-        } else {
-            if saw_alphabetic {
-                output.push('x');
-            }
-            output.push(c);
-            saw_alphabetic = false;
-        }
+        tokenizer.push(c, &mut output);
     }
+    tokenizer.finish(&mut output);
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let grouping_pattern = Regex::new(r"x( x)*").unwrap();
-    let output2 = grouping_pattern.replace_all(&output, "x");
+    #[test]
+    fn lossy_passes_only_allow_their_own_losses() {
+        let markup = create_pass("markup", &HeadingPreset::none()).unwrap();
+        assert!(markup.may_change("A line [Footnote: a note] [2] here.\n[Illustration]\n",
+                                  "A line here.\n"));
+        assert!(!markup.may_change("A line here.", "A lime here."));
 
-    return output2;
+        let unwrap = create_pass("unwrap", &HeadingPreset::none()).unwrap();
+        assert!(unwrap.may_change("A hard\nwrapped line.", "A hard wrapped line."));
+        assert!(!unwrap.may_change("A hard\nwrapped line.", "A hard wrapped lime."));
+        assert!(!unwrap.may_change("A hard wrapped line.", "A hard\nwrapped line."));
+        assert!(!unwrap.may_change("One.\n\nTwo.", "One. Two.\n\n"));
+
+        let breaks = create_pass("breaks", &HeadingPreset::none()).unwrap();
+        assert!(breaks.may_change("One.\n\n\n   *    *    *\n\nTwo.", "One.\n\n* * *\n\nTwo."));
+        assert!(!breaks.may_change("One.\n\nTwo.", "One.\n\nTwo!"));
+
+        let preset = HeadingPreset::parse("chapter roman CHAPTER\n    \
+                                           pattern CHAPTER .*\\n\\n(?P<title>.*)\n")
+                         .unwrap();
+        let headings = create_pass("headings", &preset).unwrap();
+        assert!(headings.may_change("CHAPTER I.\n\nDown the Hole\n\nText.",
+                                    "CHAPTER I\n\nUntitled\n\nText."));
+        assert!(!headings.may_change("CHAPTER I.\n\nDown the Hole\n\nText.",
+                                     "CHAPTER I\n\nUntitled\n\nTest."));
+        assert!(!headings.may_change("CHAPTER I.\n\nDown the Hole\n\nText.",
+                                     "CHAPTER II\n\nUntitled\n\nText."));
+
+        let dashes = create_pass("dashes", &HeadingPreset::none()).unwrap();
+        assert!(!dashes.may_change("A--B", "A-B"));
+    }
+
+    #[test]
+    fn double_quotes_pair_up_and_come_back_straight() {
+        let double_quotes = create_pass("double-quotes", &HeadingPreset::none()).unwrap();
+        let text = "\"Off with her head!\" said the Queen. \"Nonsense,\" said Alice.";
+        let paired = double_quotes.forward(text);
+        assert_eq!(paired,
+                   "\u{201C}Off with her head!\u{201D} said the Queen. \
+                    \u{201C}Nonsense,\u{201D} said Alice.");
+        assert_eq!(double_quotes.inverse(&paired), text);
+
+        // A quote spanning paragraphs pairs too.
+        assert_eq!(double_quotes.forward("\"One.\n\nTwo.\""),
+                   "\u{201C}One.\n\nTwo.\u{201D}");
+    }
 }
@@ -0,0 +1,158 @@
+use regex::Regex;
+use structure::{Structure, is_verse};
+
+// A block with a line longer than this wasn't hard-wrapped, and its lines end
+//  where its author ended them.
+const MAX_WRAPPED_LINE_LENGTH: usize = 80;
+
+// Join the hard-wrapped lines of each paragraph into one, so the model doesn't
+//  learn line breaks in the middle of sentences. Verse, indented blocks and
+//  blocks that weren't wrapped keep their lines, and a line starting with a
+//  heading (like an aphorism's number) is never joined onto the one before.
+
+pub fn unwrap(text: &str) -> String {
+    let break_pattern = Regex::new(r"\n([ \t]*\n)+").unwrap();
+
+    let mut output = String::new();
+    let mut block_start = 0;
+    for (break_start, break_end) in break_pattern.find_iter(text) {
+        unwrap_block(&text[block_start..break_start], &mut output);
+        output.push_str(&text[break_start..break_end]);
+        block_start = break_end;
+    }
+    unwrap_block(&text[block_start..], &mut output);
+
+    output
+}
+
+fn unwrap_block(block: &str, output: &mut String) {
+    let wrapped = block.split('\n').all(|line| line.chars().count() <= MAX_WRAPPED_LINE_LENGTH);
+    if is_verse(block) || !wrapped {
+        output.push_str(block);
+        return;
+    }
+
+    let mut lines = block.split('\n');
+    if let Some(first) = lines.next() {
+        output.push_str(first.trim_end());
+    }
+    for line in lines {
+        let line = line.trim();
+        let starts_heading = line.chars().next().is_some_and(|c| {
+            matches!(Structure::from_token(c), Some(Structure::Heading(_)))
+        });
+        if starts_heading {
+            output.push('\n');
+            output.push_str(line);
+        } else if !line.is_empty() {
+            output.push(' ');
+            output.push_str(line);
+        }
+    }
+}
+
+// Wrap each line longer than the given width at spaces, so that no line is
+//  longer than the width where possible. A line break in the text is always
+//  kept, since it marks structure (like a new aphorism); verse keeps its
+//  lines as they are.
+
+pub fn reflow(text: &str, width: usize) -> String {
+    let break_pattern = Regex::new(r"\n([ \t]*\n)+").unwrap();
+
+    let mut output = String::new();
+    let mut block_start = 0;
+    for (break_start, break_end) in break_pattern.find_iter(text) {
+        reflow_block(&text[block_start..break_start], width, &mut output);
+        output.push_str(&text[break_start..break_end]);
+        block_start = break_end;
+    }
+    reflow_block(&text[block_start..], width, &mut output);
+
+    output
+}
+
+fn reflow_block(block: &str, width: usize, output: &mut String) {
+    if is_verse(block) {
+        output.push_str(block);
+        return;
+    }
+
+    for (i, line) in block.split('\n').enumerate() {
+        if i > 0 {
+            output.push('\n');
+        }
+        if line.chars().count() > width {
+            wrap_line(line, width, output);
+        } else {
+            output.push_str(line);
+        }
+    }
+}
+
+// A wrapped line keeps its indentation on its first line.
+
+fn wrap_line(line: &str, width: usize, output: &mut String) {
+    let words = line.trim_start();
+    let indentation = &line[..line.len() - words.len()];
+    output.push_str(indentation);
+
+    let mut line_length = indentation.chars().count();
+    let mut line_empty = true;
+    for word in words.split(' ') {
+        let word_length = word.chars().count();
+        if !line_empty && line_length + 1 + word_length > width {
+            output.push('\n');
+            line_length = 0;
+        } else if !line_empty {
+            output.push(' ');
+            line_length += 1;
+        }
+        output.push_str(word);
+        line_length += word_length;
+        line_empty = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use headings::HeadingKind;
+
+    #[test]
+    fn paragraphs_are_unwrapped_but_verse_is_not() {
+        let text = "A hard-wrapped paragraph of prose, whose lines run on for long enough\n\
+                    to be prose and not verse.\n\nShort lines\nof verse\nstay.";
+        assert_eq!(unwrap(text),
+                   "A hard-wrapped paragraph of prose, whose lines run on for long enough \
+                    to be prose and not verse.\n\nShort lines\nof verse\nstay.");
+    }
+
+    #[test]
+    fn long_lines_and_headings_keep_their_line_breaks() {
+        let long_line = "An aphorism on a line of its own, long enough that it can't have been \
+                         hard-wrapped.";
+        let text = format!("A line before it, of prose and not verse.\n{}\nA line after it.",
+                           long_line);
+        assert_eq!(unwrap(&text), text);
+
+        let aphorism = Structure::Heading(HeadingKind::Aphorism).token();
+        let text = format!("A short line of prose\n{}The next aphorism runs on and on.", aphorism);
+        assert_eq!(unwrap(&text), text);
+    }
+
+    #[test]
+    fn every_long_line_is_wrapped() {
+        let text = "1. The first aphorism runs on for long enough that it is not verse.\n\
+                    2. And the second one does too, though it is not a paragraph alone.\n\n\
+                    Short lines\nof verse\nstay.";
+        assert_eq!(reflow(text, 40),
+                   "1. The first aphorism runs on for long\nenough that it is not verse.\n\
+                    2. And the second one does too, though\nit is not a paragraph alone.\n\n\
+                    Short lines\nof verse\nstay.");
+    }
+
+    #[test]
+    fn wrapped_lines_keep_their_indentation() {
+        assert_eq!(reflow("    One two three four", 12), "    One two\nthree four");
+    }
+}
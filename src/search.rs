@@ -0,0 +1,493 @@
+use std::cmp;
+use std::thread;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, Sender, Receiver};
+use generate_text::{Args, Generator};
+use gather_stats::OrderStats;
+use form_watcher::FormWatcher;
+use form_template::FormTemplate;
+use structure::Token;
+use sentence::last_sentence_end;
+
+// A generated token, along with the text order and form order that were in
+//  use when it was generated (used for color coding html output).
+pub type OutputItem = (Token, usize, usize);
+
+const MIN_FORM_COHERENCE: usize = 15;
+
+// How many times the output amount the search may run to while the planned
+//  chapters haven't all begun.
+const MAX_OVERRUN: usize = 2;
+
+struct Worker<'a> {
+    generator: Generator<'a>,
+    watcher: FormWatcher<'a>,
+    items: Vec<OutputItem>,
+    off_template: bool,
+}
+
+impl<'a> Worker<'a> {
+    // Generate characters until the form watcher reports the end of a form
+    //  sequence (or until the template is complete).
+    //
+    // With a template, only characters which keep the form on track are
+    //  chosen. If there are none, the worker stops short, off the template,
+    //  so that what it did generate still follows the template.
+
+    fn generate_segment(&mut self, template: Option<&FormTemplate>) {
+        self.items.clear();
+        self.off_template = false;
+        loop {
+            let next_item = if let Some(template) = template {
+                let watcher = &self.watcher;
+                match self.generator.next_where(|c| watcher.fits(template, c)) {
+                    Some(next_item) => next_item,
+                    None => {
+                        self.off_template = true;
+                        break;
+                    }
+                }
+            } else {
+                self.generator.next()
+            };
+            let next_watch_item = self.watcher.watch(next_item.0.to_char());
+            self.items.push((next_item.0, next_item.1, next_watch_item.0));
+            if next_watch_item.1 {
+                break;
+            }
+            if let Some(template) = template {
+                if self.watcher.form_length() >= template.len() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+// Threads which take workers (already synced to the current state) from the
+//  job queue, generate a segment with them, and send them back along with
+//  their index.
+struct WorkerPool<'a> {
+    jobs: Sender<(usize, Worker<'a>)>,
+    results: Receiver<(usize, Worker<'a>)>,
+}
+
+// A stretch of output which has been committed, but may still be rolled back.
+//  The generator and watcher hold the state from *before* the stretch was
+//  generated, so restoring them undoes it.
+struct Segment<'a> {
+    generator: Generator<'a>,
+    watcher: FormWatcher<'a>,
+    items: Vec<OutputItem>,
+}
+
+pub struct Search<'a> {
+    // immutables:
+    text_stats: &'a Vec<OrderStats<'a>>,
+    form_stats: &'a Vec<OrderStats<'a>>,
+    args: &'a Args,
+    form_max_order: usize,
+    template: Option<&'a FormTemplate>,
+
+    // current state:
+    generator: Generator<'a>,
+    watcher: FormWatcher<'a>,
+    workers: Vec<Worker<'a>>,
+    history: VecDeque<Segment<'a>>,
+    committed_amount: usize,
+}
+
+impl<'a> Search<'a> {
+    pub fn new(text_stats: &'a Vec<OrderStats<'a>>,
+               form_stats: &'a Vec<OrderStats<'a>>,
+               args: &'a Args,
+               form_max_order: usize,
+               template: Option<&'a FormTemplate>)
+               -> Search<'a> {
+
+        // Each worker draws from its own stream of random numbers, so the
+        //  outcome does not depend on how the workers are scheduled.
+        let mut workers = Vec::new();
+        for i in 0..args.max_tries {
+            let mut generator = Generator::new(text_stats,
+                                               args,
+                                               args.lower_order_bound,
+                                               args.higher_order_bound);
+            generator.set_stream(args.seed, i as u32 + 1);
+            workers.push(Worker {
+                generator,
+                watcher: FormWatcher::new(form_stats, form_max_order, &args.form_alphabet),
+                items: Vec::new(),
+                off_template: false,
+            });
+        }
+
+        Search {
+            text_stats,
+            form_stats,
+            args,
+            form_max_order,
+            template,
+
+            generator: Generator::new(text_stats,
+                                      args,
+                                      args.lower_order_bound,
+                                      args.higher_order_bound),
+            watcher: FormWatcher::new(form_stats, form_max_order, &args.form_alphabet),
+            workers,
+            history: VecDeque::new(),
+            committed_amount: 0,
+        }
+    }
+
+    // Generate text, beginning with the given starting key, until the
+    //  requested output amount is reached, a sentence has ended and every
+    //  planned chapter has begun (or, with a template, until the template
+    //  has been filled).
+    //
+    // When none of the workers manages to raise form coherence, the search
+    //  rolls back some of the recently committed segments and tries again
+    //  from there. How far back it may go is bounded by the backtrack depth,
+    //  and how many times it may do so while repairing a single stretch is
+    //  bounded by the backtrack budget.
+    //
+    // Candidates are generated on a pool of threads when more than one
+    //  thread is requested.
+
+    pub fn run(&mut self, starting_key: &str) -> Vec<OutputItem> {
+        let threads = cmp::min(self.args.threads, self.workers.len());
+        if threads <= 1 {
+            return self.search(starting_key, None);
+        }
+
+        let (job_sender, job_receiver) = channel();
+        let (result_sender, result_receiver) = channel();
+        let job_receiver: Arc<Mutex<Receiver<(usize, Worker<'a>)>>> =
+            Arc::new(Mutex::new(job_receiver));
+
+        let template = self.template;
+        thread::scope(|scope| {
+            for _ in 0..threads {
+                let job_receiver = job_receiver.clone();
+                let result_sender = result_sender.clone();
+                scope.spawn(move || {
+                    loop {
+                        let job = job_receiver.lock().unwrap().recv();
+                        if let Ok((i, mut worker)) = job {
+                            worker.generate_segment(template);
+                            let _ = result_sender.send((i, worker));
+                        } else {
+                            break;
+                        }
+                    }
+                });
+            }
+
+            // The pool is dropped at the end of the search, which closes the
+            //  job queue and lets the threads finish.
+            let pool = WorkerPool {
+                jobs: job_sender,
+                results: result_receiver,
+            };
+            self.search(starting_key, Some(&pool))
+        })
+    }
+
+    fn search(&mut self, starting_key: &str, pool: Option<&WorkerPool<'a>>) -> Vec<OutputItem> {
+        let mut output = Vec::new();
+
+        self.generator.start(Some(starting_key));
+        for c in starting_key.chars() {
+            self.watcher.watch(c);
+            output.push((Token::from_char(c), self.args.higher_order_bound, self.form_max_order));
+            self.committed_amount += 1;
+        }
+
+        let mut retreat = 0;
+        let mut budget = self.args.backtrack_budget;
+        let mut repair_mark = 0;
+
+        loop {
+            // Once the search has moved past the stretch it was repairing,
+            //  it gets a fresh budget for the next one.
+            if self.committed_amount > repair_mark {
+                retreat = 0;
+                budget = self.args.backtrack_budget;
+            }
+
+            let (chosen, success) = self.try_workers(pool);
+
+            if !success {
+                let max_retreat = cmp::min(self.args.backtrack_depth, self.history.len());
+                if budget > 0 && max_retreat > 0 {
+                    repair_mark = cmp::max(repair_mark, self.committed_amount);
+                    retreat = cmp::min(retreat + 1, max_retreat);
+                    budget -= 1;
+                    self.roll_back(retreat);
+                    println!("B {}", retreat);
+                    continue;
+                }
+                // Every worker is stuck where it started, so the template
+                //  can't be followed any further.
+                if self.template.is_some() && self.workers[chosen].items.is_empty() {
+                    println!("The template could not be followed past form symbol {}.",
+                             self.watcher.form_length());
+                    break;
+                }
+                print!("N");
+            } else {
+                print!("Y");
+            }
+            if let Some(item) = self.workers[chosen].items.iter().next_back() {
+                println!(" {}", item.2);
+            }
+
+            let sentence_count = self.generator.sentence_count();
+            self.commit(chosen, &mut output);
+
+            if let Some(template) = self.template {
+                if self.watcher.form_length() >= template.len() {
+                    break;
+                }
+            } else if self.committed_amount >= self.args.output_amount &&
+               (self.generator.ends_sentence() ||
+                self.generator.sentence_count() > sentence_count) &&
+               (self.generator.chapter_breaks_remaining() == 0 ||
+                self.committed_amount >= self.args.output_amount * MAX_OVERRUN) {
+                break;
+            }
+        }
+
+        while let Some(segment) = self.history.pop_front() {
+            output.extend(segment.items);
+        }
+        // A segment usually runs on into the next word, so the output is cut
+        //  back to the end of the sentence that ended in it.
+        if self.template.is_none() {
+            if let Some(end) = last_sentence_end(output.iter().map(|item| item.0.to_char())) {
+                output.truncate(end);
+            }
+        }
+
+        output
+    }
+
+    // Run each worker from the current state until it finishes a form
+    //  sequence. Returns the first worker which succeeded in keeping the form
+    //  coherent, or the most coherent worker if none did.
+    //
+    // Every worker runs each time (even after one has succeeded) so that the
+    //  random number streams advance the same way whether or not a pool is
+    //  used.
+
+    fn try_workers(&mut self, pool: Option<&WorkerPool<'a>>) -> (usize, bool) {
+        for worker in self.workers.iter_mut() {
+            worker.generator.sync(&self.generator);
+            worker.watcher.sync(&self.watcher);
+        }
+
+        if let Some(pool) = pool {
+            let count = self.workers.len();
+            let workers = std::mem::take(&mut self.workers);
+            for (i, worker) in workers.into_iter().enumerate() {
+                pool.jobs.send((i, worker)).unwrap();
+            }
+
+            let mut finished: Vec<Option<Worker<'a>>> = (0..count).map(|_| None).collect();
+            for _ in 0..count {
+                let (i, worker) = pool.results.recv().unwrap();
+                finished[i] = Some(worker);
+            }
+            self.workers = finished.into_iter().map(|worker| worker.unwrap()).collect();
+        } else {
+            for worker in self.workers.iter_mut() {
+                worker.generate_segment(self.template);
+            }
+        }
+
+        let mut best = 0;
+        let mut best_order = 0;
+        // With a template the form is already decided, so any worker which
+        //  kept to it will do, or else the one which got furthest along it.
+        if self.template.is_some() {
+            if let Some(i) = self.workers.iter().position(|worker| !worker.off_template) {
+                return (i, true);
+            }
+            for (i, worker) in self.workers.iter().enumerate() {
+                if worker.watcher.form_length() > self.workers[best].watcher.form_length() {
+                    best = i;
+                }
+            }
+            return (best, false);
+        }
+
+        // Chapter breaks are only made as planned, and the form around them
+        //  is often new to the form watcher, so a worker which made one is
+        //  taken over any other.
+        let chapter_breaks = self.generator.chapter_breaks();
+        let made_break = self.workers
+                             .iter()
+                             .position(|worker| worker.generator.chapter_breaks() > chapter_breaks);
+        if let Some(i) = made_break {
+            return (i, true);
+        }
+
+        for (i, worker) in self.workers.iter().enumerate() {
+            let coherence_raised = worker.watcher.current_order > self.watcher.current_order;
+            let coherence_above_min = worker.watcher.current_order >= MIN_FORM_COHERENCE;
+
+            if coherence_raised || coherence_above_min {
+                return (i, true);
+            } else if worker.watcher.current_order > best_order {
+                best = i;
+                best_order = worker.watcher.current_order;
+            }
+        }
+
+        (best, false)
+    }
+
+    // Accept a worker's output. Segments which are older than the backtrack
+    //  depth can no longer be rolled back, so they are moved to the output.
+
+    fn commit(&mut self, chosen: usize, output: &mut Vec<OutputItem>) {
+        let mut segment = Segment {
+            generator: Generator::new(self.text_stats,
+                                      self.args,
+                                      self.args.lower_order_bound,
+                                      self.args.higher_order_bound),
+            watcher: FormWatcher::new(self.form_stats,
+                                      self.form_max_order,
+                                      &self.args.form_alphabet),
+            items: Vec::new(),
+        };
+        segment.generator.sync(&self.generator);
+        segment.watcher.sync(&self.watcher);
+        segment.items.extend(self.workers[chosen].items.iter().cloned());
+
+        self.generator.sync(&self.workers[chosen].generator);
+        self.watcher.sync(&self.workers[chosen].watcher);
+        self.committed_amount += segment.items.len();
+        self.history.push_back(segment);
+
+        while self.history.len() > self.args.backtrack_depth {
+            if let Some(old_segment) = self.history.pop_front() {
+                output.extend(old_segment.items);
+            }
+        }
+    }
+
+    fn roll_back(&mut self, segment_count: usize) {
+        for _ in 0..segment_count {
+            if let Some(segment) = self.history.pop_back() {
+                self.generator.sync(&segment.generator);
+                self.watcher.sync(&segment.watcher);
+                self.committed_amount -= segment.items.len();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gather_stats::gather_stats;
+    use generate_text::test_args;
+    use preprocess::extract_form;
+    use structure::line_starts;
+
+    #[test]
+    fn multi_line_templates_are_followed() {
+        let corpus = "The cat sat on the mat, and the dog lay by the door.\nA bird sang in the \
+                      tree, and the sun shone on the hill.\n"
+                         .repeat(20);
+        let mut args = test_args();
+        args.form_alphabet.separate_words = true;
+        let template_text = "The dog sat by the tree,\nand the bird sang.\nThe sun shone on.\n";
+        let template = FormTemplate::new(template_text, &args.form_alphabet);
+
+        let text_stats = gather_stats(&corpus, args.higher_order_bound);
+        let corpus_form = extract_form(&corpus, &args.form_alphabet);
+        let form_stats = gather_stats(&corpus_form, 8);
+        let starting_key = line_starts(&corpus)
+                               .iter()
+                               .map(|&start| {
+                                   corpus[start..]
+                                       .chars()
+                                       .take(args.higher_order_bound)
+                                       .collect::<String>()
+                               })
+                               .find(|key| template.fits_start(key, &args.form_alphabet))
+                               .unwrap();
+
+        let mut search = Search::new(&text_stats, &form_stats, &args, 8, Some(&template));
+        let output = search.run(&starting_key)
+                           .iter()
+                           .map(|item| item.0.to_char())
+                           .collect::<String>();
+        assert_eq!(extract_form(&output, &args.form_alphabet),
+                   extract_form(template_text, &args.form_alphabet));
+    }
+
+    const CORPUS: &str = "The cat sat on the mat, and the dog lay by the door. A bird sang in \
+                          the tree, and the sun shone on the hill. Then the cat ran off.\n";
+
+    #[test]
+    fn rolling_back_restores_the_state_before_a_segment() {
+        let corpus = CORPUS.repeat(10);
+        let mut args = test_args();
+        args.max_tries = 2;
+        args.backtrack_depth = 3;
+        let text_stats = gather_stats(&corpus, args.higher_order_bound);
+        let corpus_form = extract_form(&corpus, &args.form_alphabet);
+        let form_stats = gather_stats(&corpus_form, 8);
+        let mut search = Search::new(&text_stats, &form_stats, &args, 8, None);
+        search.generator.start(Some("The "));
+
+        let mut output = Vec::new();
+        let mut states = Vec::new();
+        for _ in 0..3 {
+            states.push((search.committed_amount, search.watcher.form_length()));
+            let (chosen, _) = search.try_workers(None);
+            search.commit(chosen, &mut output);
+        }
+        // Every segment is still within the backtrack depth.
+        assert!(output.is_empty());
+
+        search.roll_back(2);
+        assert_eq!((search.committed_amount, search.watcher.form_length()), states[1]);
+        assert_eq!(search.history.len(), 1);
+
+        // Committing past the depth moves the oldest segments to the output.
+        for _ in 0..3 {
+            let (chosen, _) = search.try_workers(None);
+            search.commit(chosen, &mut output);
+        }
+        assert_eq!(search.history.len(), 3);
+        assert!(!output.is_empty());
+    }
+
+    #[test]
+    fn threads_do_not_change_the_output() {
+        let corpus = CORPUS.repeat(10);
+        let mut args = test_args();
+        args.max_tries = 4;
+        let text_stats = gather_stats(&corpus, args.higher_order_bound);
+        let corpus_form = extract_form(&corpus, &args.form_alphabet);
+        let form_stats = gather_stats(&corpus_form, 8);
+
+        let mut outputs = Vec::new();
+        for &threads in [1, 3].iter() {
+            args.threads = threads;
+            let mut search = Search::new(&text_stats, &form_stats, &args, 8, None);
+            let output = search.run("The ")
+                               .iter()
+                               .map(|item| item.0.to_char())
+                               .collect::<String>();
+            outputs.push(output);
+        }
+        assert!(outputs[0].len() > 4);
+        assert_eq!(outputs[0], outputs[1]);
+    }
+}
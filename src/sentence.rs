@@ -0,0 +1,262 @@
+use structure::Structure;
+
+// Words which are followed by a full stop without ending a sentence. Words
+//  listed in lower case also match when capitalized (at the start of a
+//  sentence, say), but capitalized ones only match as they are, so that
+//  "No." and "no." can be told apart.
+const ABBREVIATIONS: [&str; 38] = ["Mr", "Mrs", "Ms", "Messrs", "Mme", "Mlle", "Dr",
+                                   "Prof", "Rev", "Hon", "St", "Sr", "Jr", "Esq", "Capt",
+                                   "Col", "Gen", "Lt", "Sgt", "Maj", "Gov", "Pres", "Mt",
+                                   "Ft", "No", "Nos", "Vol", "Vols", "Ch", "Chap", "Fig",
+                                   "cf", "viz", "vs", "e.g", "i.e", "pp", "approx"];
+
+#[derive(Debug,Eq,PartialEq,Copy,Clone)]
+enum State {
+    // At the start of the text or after a break, before any text.
+    Between,
+    InSentence,
+    // After a full stop, question mark or exclamation mark which could end
+    //  the sentence, and any closing quotes or brackets.
+    Terminated,
+    // After that and some whitespace. Whether the sentence really ended is
+    //  decided by what comes next: "Oh! said Alice" goes on.
+    TerminatedSpaced,
+}
+
+// Follows text a character at a time, finding where its sentences begin and
+//  end and counting the words of the current one.
+#[derive(Debug,Clone)]
+pub struct SentenceWatcher {
+    state: State,
+    word: String,
+    words: usize,
+    sentences: usize,
+}
+
+impl SentenceWatcher {
+    pub fn new() -> SentenceWatcher {
+        SentenceWatcher {
+            state: State::Between,
+            word: String::new(),
+            words: 0,
+            sentences: 0,
+        }
+    }
+
+    pub fn sync(&mut self, target: &SentenceWatcher) {
+        self.word.clear();
+        self.word.push_str(&target.word);
+
+        self.state = target.state;
+        self.words = target.words;
+        self.sentences = target.sentences;
+    }
+
+    // Watch the next character (or structure token) of the text. Returns
+    //  whether it begins a new sentence.
+
+    pub fn push(&mut self, c: char) -> bool {
+        match Structure::from_token(c) {
+            Some(Structure::EmphasisStart) | Some(Structure::EmphasisEnd) => return false,
+            Some(_) => {
+                // Headings and breaks always end a sentence.
+                self.state = State::Between;
+                self.word.clear();
+                return false;
+            }
+            None => (),
+        }
+
+        if c.is_whitespace() {
+            self.word.clear();
+            if self.state == State::Terminated {
+                self.state = State::TerminatedSpaced;
+            }
+            return false;
+        }
+
+        let begins = match self.state {
+            State::Between => true,
+            State::TerminatedSpaced => c.is_uppercase() || c.is_numeric() || is_opener(c),
+            _ => false,
+        };
+        if begins {
+            self.words = 0;
+            self.sentences += 1;
+        }
+
+        let ends = self.would_end(c);
+        self.state = match self.state {
+            _ if ends => State::Terminated,
+            // Like the first stop in "3.5" or "U.S.", which is no ending.
+            State::Terminated if !is_terminator(c) && !is_closer(c) => State::InSentence,
+            State::Terminated => State::Terminated,
+            _ => State::InSentence,
+        };
+
+        if c.is_alphanumeric() && !self.word.chars().any(|c| c.is_alphanumeric()) {
+            self.words += 1;
+        }
+        self.word.push(c);
+
+        begins
+    }
+
+    // Whether the given character would end the current sentence (unless
+    //  the text after it carries on in lower case).
+
+    pub fn would_end(&self, c: char) -> bool {
+        match self.state {
+            State::InSentence | State::TerminatedSpaced if is_terminator(c) => {
+                c != '.' || !is_abbreviation(&self.word)
+            }
+            _ => false,
+        }
+    }
+
+    // Whether the text so far ends at the end of a sentence.
+
+    pub fn ends_sentence(&self) -> bool {
+        self.state != State::InSentence
+    }
+
+    // The number of sentences begun so far.
+
+    pub fn sentence_count(&self) -> usize {
+        self.sentences
+    }
+
+    // The number of words in the current sentence so far.
+
+    pub fn word_count(&self) -> usize {
+        self.words
+    }
+}
+
+fn is_terminator(c: char) -> bool {
+    matches!(c, '.' | '!' | '?' | '…')
+}
+
+fn is_closer(c: char) -> bool {
+    matches!(c, '"' | '\'' | '”' | '’' | ')' | ']')
+}
+
+fn is_opener(c: char) -> bool {
+    matches!(c, '"' | '\'' | '“' | '‘' | '(' | '[')
+}
+
+// Whether a word (as it appears before a full stop) is an abbreviation or an
+//  initial. "I" is left out of the initials, as it's more often the pronoun.
+
+fn is_abbreviation(word: &str) -> bool {
+    let word = word.trim_start_matches(|c: char| !c.is_alphanumeric());
+
+    let mut chars = word.chars();
+    if let (Some(first), None) = (chars.next(), chars.next()) {
+        return first.is_uppercase() && first != 'I';
+    }
+
+    ABBREVIATIONS.iter().any(|abbreviation| {
+        if *abbreviation == word {
+            return true;
+        }
+        let mut letters = abbreviation.chars();
+        match letters.next() {
+            Some(first) if first.is_lowercase() => {
+                let capitalized = first.to_uppercase().chain(letters).collect::<String>();
+                capitalized == word
+            }
+            _ => false,
+        }
+    })
+}
+
+// The length of the longest prefix of some text which ends at the end of a
+//  sentence, or None if no sentence in it ends. A sentence ends with its
+//  text, not with a piece of structure (like a heading) after it.
+
+pub fn last_sentence_end<I: Iterator<Item = char>>(text: I) -> Option<usize> {
+    let mut watcher = SentenceWatcher::new();
+    let mut end = None;
+    for (i, c) in text.enumerate() {
+        watcher.push(c);
+        if watcher.ends_sentence() && !c.is_whitespace() && Structure::from_token(c).is_none() {
+            end = Some(i + 1);
+        }
+    }
+    end
+}
+
+// The byte offsets at which the sentences of a text begin.
+
+pub fn sentence_starts(text: &str) -> Vec<usize> {
+    let mut watcher = SentenceWatcher::new();
+    text.char_indices()
+        .filter(|&(_, c)| watcher.push(c))
+        .map(|(offset, _)| offset)
+        .collect()
+}
+
+// The number of words in each sentence of a text.
+
+pub fn sentence_lengths(text: &str) -> Vec<usize> {
+    let mut watcher = SentenceWatcher::new();
+    let mut lengths = Vec::new();
+    for c in text.chars() {
+        let words = watcher.word_count();
+        if watcher.push(c) && words > 0 {
+            lengths.push(words);
+        }
+    }
+    if watcher.word_count() > 0 {
+        lengths.push(watcher.word_count());
+    }
+    lengths
+}
+
+pub fn mean_sentence_length(text: &str) -> usize {
+    let lengths = sentence_lengths(text);
+    if lengths.is_empty() {
+        return 0;
+    }
+    lengths.iter().sum::<usize>() / lengths.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use headings::HeadingKind;
+
+    fn sentences(text: &str) -> Vec<&str> {
+        let mut starts = sentence_starts(text);
+        starts.push(text.len());
+        starts.windows(2).map(|pair| text[pair[0]..pair[1]].trim()).collect()
+    }
+
+    #[test]
+    fn abbreviations_and_initials_do_not_end_sentences() {
+        assert_eq!(sentences("Mr. Dodgson met Dr. A. Liddell. They talked. It was 3.5 miles."),
+                   vec!["Mr. Dodgson met Dr. A. Liddell.", "They talked.", "It was 3.5 miles."]);
+        // "No." ends a sentence, but "No. 5" doesn't.
+        assert_eq!(sentences("She said no. Then No. 5 came."),
+                   vec!["She said no.", "Then No. 5 came."]);
+    }
+
+    #[test]
+    fn sentences_go_on_in_lower_case() {
+        assert_eq!(sentences("\u{201C}Oh!\u{201D} said Alice. \u{201C}Why?\u{201D} she asked."),
+                   vec!["\u{201C}Oh!\u{201D} said Alice.", "\u{201C}Why?\u{201D} she asked."]);
+    }
+
+    #[test]
+    fn breaks_end_sentences() {
+        let text = format!("A heading{}Text begins here", Structure::Paragraph.token());
+        assert_eq!(sentence_starts(&text).len(), 2);
+        assert_eq!(sentence_lengths("One two three. Four five. Six"), vec![3, 2, 1]);
+        assert_eq!(last_sentence_end("One two. Three".chars()), Some(8));
+
+        let heading = Structure::Heading(HeadingKind::Chapter).token();
+        let text = format!("One two.{}Three", heading);
+        assert_eq!(last_sentence_end(text.chars()), Some(8));
+    }
+}
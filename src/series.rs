@@ -0,0 +1,186 @@
+use std::cmp;
+use std::path::Path;
+use generate_text::Args;
+use headings::{Numbering, format_number};
+use title_generator::TitlePieces;
+
+// How many titles to try for a volume whose title isn't the series's own.
+const MAX_TRIES: usize = 20;
+
+// A series of books generated from the same model, under one series title
+//  and one author's name.
+pub struct Series {
+    pub title: String,
+    pub author: String,
+    pub volumes: Vec<Volume>,
+}
+
+// A volume of a series, with the settings it was generated with and some
+//  statistics about what came out.
+pub struct Volume {
+    pub number: usize,
+    pub title: String,
+    pub file_name: String,
+    pub seed: u32,
+    pub lower_order_bound: usize,
+    pub higher_order_bound: usize,
+    pub max_tries: usize,
+    pub distortion_factor: i32,
+    pub stats: BookStats,
+}
+
+// Statistics about a generated book.
+#[derive(Debug,Clone,Copy,Default)]
+pub struct BookStats {
+    pub characters: usize,
+    pub words: usize,
+    pub chapters: usize,
+    // The mean order that the text, and its form, were generated at.
+    pub mean_order: f64,
+    pub mean_form_order: f64,
+}
+
+impl Series {
+    pub fn new(titles: &TitlePieces) -> Series {
+        Series {
+            title: titles.generate_title(),
+            author: titles.generate_author(),
+            volumes: Vec::new(),
+        }
+    }
+
+    // A title for the next volume, different from the series's own.
+
+    pub fn generate_volume_title(&self, titles: &TitlePieces) -> String {
+        let mut title = titles.generate_title();
+        for _ in 0..MAX_TRIES {
+            if title != self.title {
+                break;
+            }
+            title = titles.generate_title();
+        }
+        title
+    }
+
+    // The title a volume's book is written under, like "The Mad Hatter,
+    //  Volume II: The Pool of Tears".
+
+    pub fn full_title(&self, number: usize, volume_title: &str) -> String {
+        format!("{}, Volume {}: {}",
+                self.title,
+                format_number(number, Numbering::Roman),
+                volume_title)
+    }
+
+    // A manifest of the series: each volume's file, settings and statistics.
+
+    pub fn manifest(&self) -> String {
+        let mut manifest = String::new();
+        manifest.push_str(&format!("Series: {}\nAuthor: {}\nVolumes: {}\n",
+                                   self.title,
+                                   self.author,
+                                   self.volumes.len()));
+        for volume in self.volumes.iter() {
+            manifest.push_str(&format!("\nVolume {}: {}\n", volume.number, volume.title));
+            manifest.push_str(&format!("  File: {}\n", volume.file_name));
+            manifest.push_str(&format!("  Seed: {}\n", volume.seed));
+            manifest.push_str(&format!("  Orders: {} to {}\n",
+                                       volume.lower_order_bound,
+                                       volume.higher_order_bound));
+            manifest.push_str(&format!("  Tries: {}\n", volume.max_tries));
+            manifest.push_str(&format!("  Distortion factor: {}\n", volume.distortion_factor));
+            manifest.push_str(&format!("  Characters: {}\n", volume.stats.characters));
+            manifest.push_str(&format!("  Words: {}\n", volume.stats.words));
+            manifest.push_str(&format!("  Chapters: {}\n", volume.stats.chapters));
+            manifest.push_str(&format!("  Mean order: {:.2}\n", volume.stats.mean_order));
+            manifest.push_str(&format!("  Mean form order: {:.2}\n",
+                                       volume.stats.mean_form_order));
+        }
+        manifest
+    }
+}
+
+// The settings for a volume (numbered from 1) of a series of the given
+//  number of volumes. Each volume has a seed of its own, and the lowest order
+//  it may fall back to steps down from one below the highest order, for the
+//  first volume, to the given lowest order, for the last: the volumes go from
+//  the most faithful to the source to the most freely invented.
+
+pub fn volume_args(args: &Args, number: usize, volume_count: usize) -> Args {
+    let mut volume = args.clone();
+    volume.seed = args.seed.wrapping_add(number as u32 - 1);
+    volume.output_filename = volume_file_name(&args.output_filename, number);
+
+    let highest_lower_bound = cmp::max(args.higher_order_bound, args.lower_order_bound + 1) - 1;
+    let steps = highest_lower_bound - args.lower_order_bound;
+    volume.lower_order_bound = if volume_count > 1 {
+        highest_lower_bound - (steps * (number - 1) + (volume_count - 1) / 2) / (volume_count - 1)
+    } else {
+        args.lower_order_bound
+    };
+    volume
+}
+
+// A volume's file is named after the output file, with its number: volume 2
+//  of "output.txt" goes in "output-2.txt".
+
+pub fn volume_file_name(output_file_name: &str, number: usize) -> String {
+    let path = Path::new(output_file_name);
+    let stem = path.file_stem().map_or(String::from("output"),
+                                       |stem| stem.to_string_lossy().into_owned());
+    let file_name = match path.extension() {
+        Some(extension) => format!("{}-{}.{}", stem, number, extension.to_string_lossy()),
+        None => format!("{}-{}", stem, number),
+    };
+    path.with_file_name(file_name).to_string_lossy().into_owned()
+}
+
+// The manifest of a series goes next to its volumes, with the extension
+//  ".manifest".
+
+pub fn manifest_file_name(output_file_name: &str) -> String {
+    let path = Path::new(output_file_name).with_extension("manifest");
+    path.to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use generate_text::test_args;
+
+    #[test]
+    fn volumes_go_from_faithful_to_freely_invented() {
+        let args = test_args();
+        let volumes: Vec<Args> = (1..5).map(|number| volume_args(&args, number, 4)).collect();
+
+        let seeds: Vec<u32> = volumes.iter().map(|volume| volume.seed).collect();
+        assert_eq!(seeds, vec![1, 2, 3, 4]);
+        let lower_bounds: Vec<usize> =
+            volumes.iter().map(|volume| volume.lower_order_bound).collect();
+        assert_eq!(lower_bounds, vec![3, 2, 2, 1]);
+        assert!(volumes.iter().all(|volume| volume.higher_order_bound == 4));
+        assert_eq!(volumes[1].output_filename, "output-2.txt");
+
+        assert_eq!(volume_args(&args, 1, 1).lower_order_bound, 1);
+    }
+
+    #[test]
+    fn volumes_and_manifests_are_named_after_the_output_file() {
+        assert_eq!(volume_file_name("output.txt", 2), "output-2.txt");
+        assert_eq!(volume_file_name("books/output.tex", 3), "books/output-3.tex");
+        assert_eq!(volume_file_name("output", 1), "output-1");
+        assert_eq!(manifest_file_name("output.txt"), "output.manifest");
+        assert_eq!(manifest_file_name("books/output"), "books/output.manifest");
+    }
+
+    #[test]
+    fn volumes_are_titled_under_the_series() {
+        let series = Series {
+            title: String::from("The Mad Hatter"),
+            author: String::from("E. M. Lockley"),
+            volumes: Vec::new(),
+        };
+        assert_eq!(series.full_title(2, "The Pool of Tears"),
+                   "The Mad Hatter, Volume II: The Pool of Tears");
+    }
+}
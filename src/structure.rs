@@ -0,0 +1,184 @@
+use regex::Regex;
+use headings::HeadingKind;
+
+// The structure of a book (headings, breaks between paragraphs and stanzas,
+//  scene breaks, emphasis) is carried through preprocessing and generation as tokens:
+//  single characters from the Unicode private use area, which no corpus is
+//  expected to contain. The generator learns them like any other character,
+//  but emits them as structure events rather than text.
+
+const TOKEN_BASE: u32 = 0xE000;
+const TOKEN_END: u32 = 0xF8FF;
+
+// Lines of a block of verse are short, or mostly indented.
+const MAX_VERSE_LINE_LENGTH: usize = 60;
+
+#[derive(Debug,Eq,PartialEq,Copy,Clone)]
+pub enum Structure {
+    Heading(HeadingKind),
+    SceneBreak,
+    Paragraph,
+    Stanza,
+    EmphasisStart,
+    EmphasisEnd,
+}
+
+impl Structure {
+    pub fn token(&self) -> char {
+        let offset = match *self {
+            Structure::Heading(HeadingKind::Part) => 0x00,
+            Structure::Heading(HeadingKind::Chapter) => 0x01,
+            Structure::Heading(HeadingKind::Section) => 0x02,
+            Structure::Heading(HeadingKind::Aphorism) => 0x03,
+            Structure::SceneBreak => 0x10,
+            Structure::Paragraph => 0x11,
+            Structure::Stanza => 0x12,
+            Structure::EmphasisStart => 0x20,
+            Structure::EmphasisEnd => 0x21,
+        };
+        ::std::char::from_u32(TOKEN_BASE + offset).unwrap()
+    }
+
+    pub fn from_token(token: char) -> Option<Structure> {
+        let offset = (token as u32).wrapping_sub(TOKEN_BASE);
+        let structure = match offset {
+            0x00 => Structure::Heading(HeadingKind::Part),
+            0x01 => Structure::Heading(HeadingKind::Chapter),
+            0x02 => Structure::Heading(HeadingKind::Section),
+            0x03 => Structure::Heading(HeadingKind::Aphorism),
+            0x10 => Structure::SceneBreak,
+            0x11 => Structure::Paragraph,
+            0x12 => Structure::Stanza,
+            0x20 => Structure::EmphasisStart,
+            0x21 => Structure::EmphasisEnd,
+            _ => return None,
+        };
+        Some(structure)
+    }
+}
+
+// Something the generator emits: either a character of text or a piece of
+//  structure.
+#[derive(Debug,Eq,PartialEq,Copy,Clone)]
+pub enum Token {
+    Text(char),
+    Structure(Structure),
+}
+
+impl Token {
+    pub fn from_char(c: char) -> Token {
+        match Structure::from_token(c) {
+            Some(structure) => Token::Structure(structure),
+            None => Token::Text(c),
+        }
+    }
+
+    pub fn to_char(self) -> char {
+        match self {
+            Token::Text(c) => c,
+            Token::Structure(structure) => structure.token(),
+        }
+    }
+}
+
+pub fn is_reserved(c: char) -> bool {
+    let code = c as u32;
+    (TOKEN_BASE..=TOKEN_END).contains(&code)
+}
+
+// Remove any characters a corpus contains which are reserved for tokens, so
+//  they can't be mistaken for structure.
+
+pub fn strip_reserved(text: &str) -> String {
+    text.chars().filter(|c| !is_reserved(*c)).collect()
+}
+
+// Replace scene breaks ("* * *" on a line of their own) with tokens.
+
+pub fn mark_scene_breaks(text: &str) -> String {
+    let scene_break_pattern = Regex::new(r"(?m)^[ \t]*(\*[ \t]*){3,}$").unwrap();
+    let scene_break_token = Structure::SceneBreak.token().to_string();
+    scene_break_pattern.replace_all(text, &scene_break_token[..])
+}
+
+// Replace scene breaks ("* * *" on a line of their own) and the blank lines
+//  between blocks of text with tokens. A break after a block of verse is a
+//  stanza break, any other is a paragraph break.
+
+pub fn mark_breaks(text: &str) -> String {
+    let break_pattern = Regex::new(r"\n([ \t]*\n)+").unwrap();
+
+    let text = mark_scene_breaks(text);
+
+    let mut output = String::new();
+    let mut block_start = 0;
+    for (break_start, break_end) in break_pattern.find_iter(&text) {
+        let block = &text[block_start..break_start];
+        output.push_str(block);
+        if is_verse(block) {
+            output.push(Structure::Stanza.token());
+        } else {
+            output.push(Structure::Paragraph.token());
+        }
+        block_start = break_end;
+    }
+    output.push_str(&text[block_start..]);
+
+    output
+}
+
+// The byte offsets at which the lines of preprocessed text begin: its start,
+//  and whatever follows a newline or a break token (as blank lines between
+//  paragraphs, stanzas and scenes have become tokens).
+
+pub fn line_starts(text: &str) -> Vec<usize> {
+    let mut starts = Vec::new();
+    let mut at_start = true;
+    for (offset, c) in text.char_indices() {
+        if at_start && c != '\n' {
+            starts.push(offset);
+        }
+        at_start = match Structure::from_token(c) {
+            Some(Structure::Paragraph) | Some(Structure::Stanza) | Some(Structure::SceneBreak) => {
+                true
+            }
+            _ => c == '\n',
+        };
+    }
+    starts
+}
+
+pub fn is_verse(block: &str) -> bool {
+    let lines = block.lines().collect::<Vec<_>>();
+    if lines.len() < 2 {
+        return false;
+    }
+
+    let short = lines.iter().all(|line| line.chars().count() < MAX_VERSE_LINE_LENGTH);
+    let indented = lines.iter().filter(|line| line.starts_with(' ')).count();
+    short || indented * 2 >= lines.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_starts_follow_newlines_and_breaks() {
+        let paragraph = Structure::Paragraph.token();
+        let text = format!("'Well!' one\ntwo{}'Three,' four", paragraph);
+        let starts = line_starts(&text);
+        let lines = starts.iter().map(|&start| &text[start..start + 1]).collect::<Vec<_>>();
+        assert_eq!(lines, vec!["'", "t", "'"]);
+    }
+
+    #[test]
+    fn breaks_become_tokens() {
+        let text = mark_breaks("One.\n\nTwo.\n\nA short line\nand another\n\nEnd.");
+        let expected = format!("One.{}Two.{}A short line\nand another{}End.",
+                               Structure::Paragraph.token(),
+                               Structure::Paragraph.token(),
+                               Structure::Stanza.token());
+        assert_eq!(text, expected);
+    }
+}
@@ -1,355 +1,395 @@
-
-use std::collections::HashSet;
-use generate_text::pick_random_in_range;
-use regex::Regex;
-
-#[derive(Debug,Eq,PartialEq,Hash,Copy,Clone)]
-enum WordType {
-    Noun(NounType),
-    Pronoun,
-    Adjective,
-    Verb,
-    Preposition,
-    Article,
-    Conjunction,
-    Punctuation,
-}
-
-#[derive(Debug,Eq,PartialEq,Hash,Copy,Clone)]
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use grammar::{Grammar, title_case};
+use vocabulary::{CorpusWords, Vocabulary, derive_vocabulary};
+use title_model::TitleModel;
+use pen_name::PenNames;
+
+#[derive(Debug,Eq,PartialEq,Ord,PartialOrd,Hash,Copy,Clone)]
 enum NounType {
     Person,
     Place,
     Thing,
 }
 
-struct TitlePieces {
-    words: WordsByType,
-    templates: HashSet<Vec<WordType>>,
-}
+impl NounType {
+    // The rule in title grammars which lists nouns of this type.
 
-struct WordsByType {
-    nouns: NounsByType,
-    pronouns: HashSet<&'static str>,
-    adjectives: HashSet<&'static str>,
-    verbs: HashSet<&'static str>,
-    prepositions: HashSet<&'static str>,
-    articles: HashSet<&'static str>,
-    conjunctions: HashSet<&'static str>,
-    punctuation: HashSet<&'static str>,
+    fn rule_name(&self) -> &'static str {
+        match *self {
+            NounType::Person => "person",
+            NounType::Place => "place",
+            NounType::Thing => "thing",
+        }
+    }
 }
 
-struct NounsByType {
-    people: HashSet<&'static str>,
-    places: HashSet<&'static str>,
-    things: HashSet<&'static str>,
+// How many titles to try for one that uses what a chapter is about.
+const MAX_TRIES: usize = 20;
+
+// The rules used when a corpus has no title file, for the people, places
+//  and things found in the corpus. Only words which any English text could
+//  do with are given here; alternatives needing a list which nothing was
+//  found for are left out.
+const DERIVED_TITLES: &str = "
+preposition of, in, from, at
+article the
+conjunction and
+
+rule title = #article# #thing# #preposition# #place# | #article# #place# #preposition# #thing#
+rule title = #person.possessive# #thing# ^2 | #person# #conjunction# #article# #thing#
+rule title = #article# #thing# #conjunction# #article# #thing# | #person# #preposition# #place#
+rule title = #article# #thing# #preposition# #person# | #article# #place#
+rule title = #person.possessive# #thing# #preposition# #place# | #thing#
+";
+
+// Rules for a book's dedication (to names from the list "name") and its
+//  back cover blurb, for title files which don't give their own.
+const MATTER_RULES: &str = "
+rule dedication = For #name# | To #name# | For #name# and #name# | In memory of #name#
+rule dedication = To #name#, who would have understood | For #name#, as promised
+rule blurb = #blurb_setup# #blurb_trouble# #blurb_praise#
+rule blurb_setup = Nothing ever happens to #person#, and that is how #person# likes it.
+rule blurb_setup = #person# has spent a lifetime looking for the #thing.lower#.
+rule blurb_setup = Everyone has heard the story of #person# and the #thing.lower#.
+rule blurb_trouble = Then #person# arrives, and with #person# comes the #thing.lower#.
+rule blurb_trouble = But when the #thing.lower# goes missing, only #person# knows where to look.
+rule blurb_trouble = Soon #person.possessive# #thing.lower# is all that anyone talks about.
+rule blurb_praise = A #praise# tale of the #thing.lower# and the #thing.lower#.
+rule blurb_praise = #praise.capitalize# and #praise#, this is a book to read twice.
+rule praise = dazzling | strange | haunting | tender | unsettling | wise | riotous
+";
+
+// Titles are made by a grammar (see grammar.rs), from the rule "title", and
+//  authors' names from the rule "author" if there is one, or else made up
+//  to sound like the names in the list "name" (see pen_name.rs). Chapter
+//  titles are made from the rule
+//  "chapter" if there is one, or else by a model of the source's chapter
+//  titles if it has enough of them, or else from "title". A chapter title
+//  can also be about the chapter, naming the people and things it's about.
+pub struct TitlePieces {
+    grammar: Grammar,
+    chapter_model: Option<TitleModel>,
+    corpus_words: CorpusWords,
+    pen_names: PenNames,
 }
 
-pub fn generate_title() -> String {
-    let tp = define_title_pieces();
-    let mut output = String::new();
+impl TitlePieces {
+    // Titles which are all "Untitled", for where they don't matter.
 
-    let template_choice = pick_random_in_range(0, tp.templates.len() - 1);
-    let template = tp.templates.iter().nth(template_choice).unwrap();
+    pub fn untitled() -> TitlePieces {
+        TitlePieces::parse("rule title = Untitled").unwrap()
+    }
 
-    for word_type in template {
-        let word = match *word_type {
-            WordType::Noun(noun_type) => {
-                match noun_type {
-                    NounType::Person => pick(&tp.words.nouns.people),
-                    NounType::Place => pick(&tp.words.nouns.places),
-                    NounType::Thing => pick(&tp.words.nouns.things),
-                }
-            }
-            WordType::Pronoun => pick(&tp.words.pronouns),
-            WordType::Adjective => pick(&tp.words.adjectives),
-            WordType::Verb => pick(&tp.words.verbs),
-            WordType::Preposition => pick(&tp.words.prepositions),
-            WordType::Article => pick(&tp.words.articles),
-            WordType::Conjunction => pick(&tp.words.conjunctions),
-            WordType::Punctuation => pick(&tp.words.punctuation),
+    // Titles for a corpus without a title file, from the vocabulary found in
+    //  it: the people, places and things, and the names authors' names are
+    //  made from.
+
+    pub fn derive(vocabulary: Vocabulary) -> Result<TitlePieces, String> {
+        let mut grammar = parse_grammar(DERIVED_TITLES).unwrap();
+        let noun_sets = vec![(NounType::Person, vocabulary.people),
+                             (NounType::Place, vocabulary.places),
+                             (NounType::Thing, vocabulary.things)];
+        for (noun_type, words) in noun_sets.into_iter() {
+            grammar.add_words(noun_type.rule_name(), words.iter().map(|word| &word[..]));
+        }
+        grammar.add_words("name", vocabulary.names.iter().map(|name| &name[..]));
+        grammar.drop_unfinished();
+        if !grammar.has_rule("title") {
+            return Err(String::from("No people, places or things were found to make titles \
+                                     from; give a title file."));
+        }
+        TitlePieces::from_grammar(grammar)
+    }
+
+    // Parse a title file. A line is one of:
+    //
+    //  - "rule" followed by a rule's name, '=' and its alternatives (see
+    //    Grammar). Rules given on several lines get all of the alternatives.
+    //  - "template" followed by the names of word lists, which adds an
+    //    alternative for the title with a word from each, in order.
+    //  - The name of a word list (like "person" or "adjective") followed by
+    //    words, separated by commas. Word lists are rules too, and may be
+    //    given on several lines.
+    //
+    // Authors' names are made up after the names in the list "name", unless
+    //  the file has a rule for "author". A book's dedication and blurb come
+    //  from the rules "dedication" and "blurb". Blank lines and lines
+    //  starting with '#' are ignored.
+
+    pub fn parse(config: &str) -> Result<TitlePieces, String> {
+        TitlePieces::from_grammar(parse_grammar(config)?)
+    }
+
+    fn from_grammar(grammar: Grammar) -> Result<TitlePieces, String> {
+        if !grammar.has_rule("title") {
+            return Err(String::from("No rule or templates are given for titles."));
+        }
+        grammar.validate()?;
+
+        // The dedication and blurb have rules of their own unless the file
+        //  gives them, as far as its word lists have what those need.
+        let mut matter = parse_grammar(MATTER_RULES).unwrap();
+        matter.add_missing_rules(&grammar);
+        matter.drop_unfinished();
+        let mut grammar = grammar;
+        grammar.add_missing_rules(&matter);
+
+        let pieces = TitlePieces {
+            pen_names: PenNames::new(&grammar.words("name")),
+            grammar,
+            chapter_model: None,
+            corpus_words: CorpusWords::new(),
         };
+        Ok(pieces)
+    }
+
+    // Learn chapter titles from the titles of the source's chapters. Returns
+    //  whether there were enough of them.
+
+    pub fn learn_chapter_titles(&mut self, titles: &[String]) -> bool {
+        self.chapter_model = TitleModel::train(titles);
+        self.chapter_model.is_some()
+    }
 
-        if output.len() > 0 {
-            match *word_type {
-                WordType::Punctuation => (),
-                _ => output.push_str(" "),
+    // Count the words of the corpus, to tell what generated chapters are
+    //  about, and so that no pen name is one of the corpus's names.
+
+    pub fn count_corpus_words(&mut self, text: &str) {
+        self.corpus_words = CorpusWords::count(text);
+        self.pen_names.exclude(self.corpus_words.names().iter().map(|name| &name[..]));
+    }
+
+    // A summary of the vocabulary, like "12 people, 3 places and 40 things".
+
+    pub fn describe(&self) -> String {
+        format!("{} people, {} places and {} things",
+                self.grammar.alternative_count(NounType::Person.rule_name()),
+                self.grammar.alternative_count(NounType::Place.rule_name()),
+                self.grammar.alternative_count(NounType::Thing.rule_name()))
+    }
+
+    pub fn generate_title(&self) -> String {
+        title_case(&self.grammar.expand("title"))
+    }
+
+    pub fn generate_chapter_title(&self) -> String {
+        if self.grammar.has_rule("chapter") {
+            return title_case(&self.grammar.expand("chapter"));
+        }
+        // The model can't always make up a title of its own; the book's title
+        //  rule can.
+        if let Some(title) = self.chapter_model.as_ref().and_then(|model| model.generate()) {
+            return title;
+        }
+        self.generate_title()
+    }
+
+    // A chapter title about a generated chapter: the rule "chapter" (or
+    //  "title"), with the chapter's salient names and nouns in place of the
+    //  grammar's people, places and things. A chapter with nothing salient,
+    //  or whose titles never mention it, gets a chapter title like any other.
+
+    pub fn generate_chapter_title_about(&self, chapter: &str) -> String {
+        let salient = self.corpus_words.salient(chapter);
+        if salient.is_empty() {
+            return self.generate_chapter_title();
+        }
+
+        let (places, people): (Vec<&String>, Vec<&String>) =
+            salient.names.iter().partition(|name| {
+                self.grammar.has_word(NounType::Place.rule_name(), name)
+            });
+        let noun_sets = vec![(NounType::Person, people),
+                             (NounType::Place, places),
+                             (NounType::Thing, salient.nouns.iter().collect())];
+        let mut grammar = self.grammar.clone();
+        for (noun_type, words) in noun_sets.into_iter() {
+            if !words.is_empty() {
+                grammar.set_words(noun_type.rule_name(), words.iter().map(|word| &word[..]));
             }
         }
 
-        output.push_str(word);
+        let rule = if grammar.has_rule("chapter") { "chapter" } else { "title" };
+        for _ in 0..MAX_TRIES {
+            let title = title_case(&grammar.expand(rule));
+            let mentions = salient.names
+                                  .iter()
+                                  .chain(salient.nouns.iter())
+                                  .any(|word| title.contains(&word[..]));
+            if mentions {
+                return title;
+            }
+        }
+        self.generate_chapter_title()
     }
 
-    let capitalized_first_letter = output.chars().nth(0).unwrap().to_uppercase().next().unwrap();
-    let mut output2 = String::new();
-    output2.push(capitalized_first_letter);
-    output2.push_str(&output[1..]);
+    pub fn generate_author(&self) -> String {
+        if self.grammar.has_rule("author") {
+            return self.grammar.expand("author");
+        }
+        self.pen_names.generate()
+    }
 
-    let a_pattern = Regex::new(r"([Aa]) ([AEIOU])").unwrap();
-    let output3 = a_pattern.replace_all(&output2, "$1n $2");
+    // Expand any rule of the grammar, for other generated text (like a
+    //  blurb).
 
-    return output3;
+    pub fn expand(&self, name: &str) -> Option<String> {
+        if !self.grammar.has_rule(name) {
+            return None;
+        }
+        Some(self.grammar.expand(name))
+    }
 }
 
-pub fn generate_author() -> String {
-    let names = define_names();
-    let mut author = String::new();
-    author.push_str(pick(&names));
-    author.push(' ');
-    author.push_str(pick(&names));
-    return author;
+// Parse the lines of a title file into a grammar (see TitlePieces::parse).
+
+fn parse_grammar(config: &str) -> Result<Grammar, String> {
+    let mut grammar = Grammar::new();
+
+    for (line_number, line) in config.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (keyword, rest) = match line.find(char::is_whitespace) {
+            Some(split) => (&line[..split], line[split..].trim()),
+            None => (line, ""),
+        };
+
+        let result = match keyword {
+            "rule" => {
+                match rest.find('=') {
+                    Some(equals) => grammar.add_rule(rest[..equals].trim(), &rest[equals + 1..]),
+                    None => Err(String::from("expected '=' after the rule's name.")),
+                }
+            }
+            "template" => {
+                let symbols = rest.split_whitespace()
+                                  .map(|name| format!("#{}#", name))
+                                  .collect::<Vec<_>>();
+                if symbols.is_empty() {
+                    Err(String::from("empty template."))
+                } else {
+                    grammar.add_rule("title", &symbols.join(" "))
+                }
+            }
+            _ => {
+                let words = rest.split(',').map(|word| word.trim()).collect::<Vec<_>>();
+                if words.iter().any(|word| word.is_empty()) {
+                    Err(format!("empty word in the list for '{}'.", keyword))
+                } else {
+                    grammar.add_words(keyword, words.into_iter());
+                    Ok(())
+                }
+            }
+        };
+        result.map_err(|message| format!("Line {}: {}", line_number + 1, message))?;
+    }
+    Ok(grammar)
 }
 
-fn pick(words: &HashSet<&'static str>) -> &'static str {
-    let choice = pick_random_in_range(0, words.len() - 1);
-    return words.iter().nth(choice).unwrap();
+// The title file for a corpus sits next to it, with the extension ".titles".
+
+pub fn title_file_for(corpus_file_name: &str) -> String {
+    let path = Path::new(corpus_file_name).with_extension("titles");
+    path.to_string_lossy().into_owned()
 }
 
-fn define_title_pieces() -> TitlePieces {
-
-    let nouns_people = {
-        let mut set = HashSet::new();
-        set.insert("Pig");
-        set.insert("Rabbit");
-        set.insert("Caterpillar");
-        set.insert("Lobster");
-        set.insert("Queen's");
-        set.insert("Turtle's");
-        set.insert("Alice's");
-        set.insert("Bill");
-        set
-    };
-
-    let nouns_places = {
-        let mut set = HashSet::new();
-        set.insert("Pool");
-        set.insert("Croquet-Ground");
-        set.insert("Rabbit-Hole");
-        set.insert("Wonderland");
-        set
-    };
-
-    let nouns_things = {
-        let mut set = HashSet::new();
-        set.insert("Tears");
-        set.insert("Tale");
-        set.insert("Advice");
-        set.insert("Pepper");
-        set.insert("Story");
-        set.insert("Quadrille");
-        set.insert("Tarts");
-        set.insert("Evidence");
-        set.insert("Caucus-Race");
-        set.insert("Tea-Party");
-        set.insert("Adventures");
-        set
-    };
-
-    let pronouns = {
-        let mut set = HashSet::new();
-        set.insert("Who");
-        set
-    };
-
-    let verbs = {
-        let mut set = HashSet::new();
-        set.insert("Sends");
-        set.insert("Stole");
-        set
-    };
-
-    let adjectives = {
-        let mut set = HashSet::new();
-        set.insert("Long");
-        set.insert("Little");
-        set.insert("Mad");
-        set.insert("Mock");
-        set
-    };
-
-    let prepositions = {
-        let mut set = HashSet::new();
-        set.insert("Down");
-        set.insert("of");
-        set.insert("in");
-        set.insert("from");
-        set
-    };
-
-    let articles = {
-        let mut set = HashSet::new();
-        set.insert("a");
-        set.insert("the");
-        set
-    };
-
-    let conjunctions = {
-        let mut set = HashSet::new();
-        set.insert("and");
-        set
-    };
-
-    let punctuation = {
-        let mut set = HashSet::new();
-        set.insert("?");
-        set
-    };
-
-    let templates = {
-        let mut set = HashSet::new();
-        set.insert(vec![WordType::Preposition, WordType::Article, WordType::Noun(NounType::Place)]);
-
-        set.insert(vec![WordType::Article,
-                        WordType::Noun(NounType::Place),
-                        WordType::Preposition,
-                        WordType::Noun(NounType::Thing)]);
-
-        set.insert(vec![
-			WordType::Article,
-			WordType::Noun(NounType::Thing),
-			WordType::Conjunction, 
-			WordType::Article, 
-			WordType::Adjective, 
-			WordType::Noun(NounType::Thing),
-			]);
-
-        set.insert(vec![
-			WordType::Article,
-			WordType::Noun(NounType::Person),
-			WordType::Verb, 
-			WordType::Preposition, 
-			WordType::Article, 
-			WordType::Adjective, 
-			WordType::Noun(NounType::Person),
-			]);
-
-        set.insert(vec![WordType::Noun(NounType::Thing),
-                        WordType::Preposition,
-                        WordType::Article,
-                        WordType::Noun(NounType::Person)]);
-
-        set.insert(vec![WordType::Noun(NounType::Person),
-                        WordType::Conjunction,
-                        WordType::Noun(NounType::Thing)]);
-
-        set.insert(vec![WordType::Article, WordType::Adjective, WordType::Noun(NounType::Thing)]);
-
-        set.insert(vec![WordType::Article,
-                        WordType::Noun(NounType::Person),
-                        WordType::Noun(NounType::Place)]);
-
-        set.insert(vec![WordType::Article,
-                        WordType::Adjective,
-                        WordType::Noun(NounType::Person),
-                        WordType::Noun(NounType::Thing)]);
-
-        set.insert(vec![WordType::Article,
-                        WordType::Noun(NounType::Person),
-                        WordType::Noun(NounType::Thing)]);
-
-        set.insert(vec![
-			WordType::Pronoun,
-			WordType::Verb,
-			WordType::Article,
-			WordType::Noun(NounType::Thing),
-			WordType::Punctuation,
-			]);
-
-        set.insert(vec![WordType::Noun(NounType::Person), WordType::Noun(NounType::Thing)]);
-
-        set.insert(vec![
-			WordType::Noun(NounType::Person),
-			WordType::Noun(NounType::Thing),
-			WordType::Preposition,
-			WordType::Noun(NounType::Place),
-			]);
-
-        set
-    };
-
-    return TitlePieces {
-        words: WordsByType {
-            nouns: NounsByType {
-                people: nouns_people,
-                places: nouns_places,
-                things: nouns_things,
-            },
-            pronouns: pronouns,
-            verbs: verbs,
-            adjectives: adjectives,
-            prepositions: prepositions,
-            articles: articles,
-            conjunctions: conjunctions,
-            punctuation: punctuation,
-        },
-        templates: templates,
-    };
+// Load the title vocabulary for a corpus from its title file. If it has none,
+//  the vocabulary is derived from the corpus text.
+
+pub fn load_title_pieces(corpus_file_name: &str, corpus_text: &str) -> Result<TitlePieces, String> {
+    let title_file_name = title_file_for(corpus_file_name);
+
+    let mut config = String::new();
+    match File::open(&title_file_name) {
+        Ok(mut file) => {
+            file.read_to_string(&mut config)
+                .map_err(|error| format!("Could not read {}: {}", title_file_name, error))?;
+        }
+        Err(_) => return TitlePieces::derive(derive_vocabulary(corpus_text)),
+    }
+
+    TitlePieces::parse(&config).map_err(|message| {
+        format!("Invalid title file {}: {}", title_file_name, message)
+    })
 }
 
-fn define_names() -> HashSet<&'static str> {
-    let mut set = HashSet::new();
-
-    set.insert("Alice");
-    set.insert("Antipathies");
-    set.insert("Bill");
-    set.insert("Canary");
-    set.insert("Cat");
-    set.insert("Caterpillar");
-    set.insert("Cheshire");
-    set.insert("Cat");
-    set.insert("Conqueror");
-    set.insert("Crab");
-    set.insert("Dinah");
-    set.insert("Dodo");
-    set.insert("Dormouse");
-    set.insert("Duchess");
-    set.insert("Duck");
-    set.insert("Eaglet");
-    set.insert("Edgar");
-    set.insert("Atheling");
-    set.insert("Elsie");
-    set.insert("Lacie");
-    set.insert("Father");
-    set.insert("William");
-    set.insert("Fish-Footman");
-    set.insert("Five");
-    set.insert("Footman");
-    set.insert("Frog-Footman");
-    set.insert("Fury");
-    set.insert("Gryphon");
-    set.insert("Hatter");
-    set.insert("Jack");
-    set.insert("King");
-    set.insert("Knave");
-    set.insert("Lewis");
-    set.insert("Carroll");
-    set.insert("Little");
-    set.insert("Bill");
-    set.insert("Lizard");
-    set.insert("Lobster");
-    set.insert("Lory");
-    set.insert("Magpie");
-    set.insert("March");
-    set.insert("Hare");
-    set.insert("Mock");
-    set.insert("Turtle");
-    set.insert("Morcar");
-    set.insert("Mouse");
-    set.insert("Multiplication");
-    set.insert("Northumbria");
-    set.insert("Owl");
-    set.insert("Panther");
-    set.insert("Pepper");
-    set.insert("Pigeon");
-    set.insert("Queen");
-    set.insert("Rabbit");
-    set.insert("Seven");
-    set.insert("Shakespeare");
-    set.insert("Tillie");
-    set.insert("Turtle");
-    set.insert("Two");
-    set.insert("White");
-    set.insert("Rabbit");
-    set.insert("William");
-
-    return set;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    fn vocabulary(things: &[&str]) -> Vocabulary {
+        Vocabulary {
+            people: BTreeSet::new(),
+            places: BTreeSet::new(),
+            things: things.iter().map(|&thing| String::from(thing)).collect(),
+            names: BTreeSet::new(),
+        }
+    }
+
+    #[test]
+    fn derived_titles_only_use_what_was_found() {
+        let titles = TitlePieces::derive(vocabulary(&["Bells", "Sledges"])).unwrap();
+        assert_eq!(titles.describe(), "0 people, 0 places and 2 things");
+        let words = ["The", "the", "and", "Bells", "Sledges"];
+        for _ in 0..50 {
+            let title = titles.generate_title();
+            assert!(title.split(' ').all(|word| words.contains(&word)), "{}", title);
+        }
+        // The blurb and dedication need people and names.
+        assert_eq!(titles.expand("blurb"), None);
+        assert_eq!(titles.expand("dedication"), None);
+    }
+
+    #[test]
+    fn titles_cannot_be_derived_from_nothing() {
+        assert!(TitlePieces::derive(vocabulary(&[])).is_err());
+    }
+
+    #[test]
+    fn title_files_need_a_title_rule() {
+        assert!(TitlePieces::parse("thing Tarts").is_err());
+        assert!(TitlePieces::parse("rule title = #thing#").is_err());
+        let titles = TitlePieces::parse("thing tarts\ntemplate thing").unwrap();
+        assert_eq!(titles.generate_title(), "Tarts");
+    }
+
+    #[test]
+    fn title_files_give_lists_rules_and_templates() {
+        let config = "# People and things.\nperson Hector, Achilles\nperson Priam\n\
+                      thing shield\nrule title = #person.possessive# #thing.capitalize#\n\
+                      template person\nrule author = Homer\n";
+        let titles = TitlePieces::parse(config).unwrap();
+        assert_eq!(titles.describe(), "3 people, 0 places and 1 things");
+        assert_eq!(titles.generate_author(), "Homer");
+
+        let error = TitlePieces::parse("person Hector,\nrule title = #person#").err();
+        assert_eq!(error, Some(String::from("Line 1: empty word in the list for 'person'.")));
+        assert!(TitlePieces::parse("rule title #person#").is_err());
+    }
+
+    #[test]
+    fn shipped_title_files_parse() {
+        for name in ["input/alice.titles", "input/iliad.titles"].iter() {
+            let mut config = String::new();
+            File::open(name).unwrap().read_to_string(&mut config).unwrap();
+            assert!(TitlePieces::parse(&config).is_ok(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn chapter_titles_are_about_their_chapters() {
+        let mut titles = TitlePieces::parse("thing Tarts, Croquet\nrule title = The #thing#")
+                             .unwrap();
+        titles.count_corpus_words(&"The Queen made the tarts. The King played at the croquet, \
+                                    and the tarts were stolen. "
+                                       .repeat(3));
+        let chapter = "The Knave took the tarts. The tarts were gone, and the Queen wept.";
+        assert_eq!(titles.generate_chapter_title_about(chapter), "The Tarts");
+    }
 }
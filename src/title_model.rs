@@ -0,0 +1,137 @@
+use std::collections::{BTreeMap, BTreeSet};
+use generate_text::pick_random_in_range;
+use grammar::title_case;
+
+// How few titles are too few to learn anything from.
+const MIN_TITLES: usize = 3;
+
+// Generated titles longer than this are thrown away, as are copies of the
+//  source's titles, for up to so many tries.
+const MAX_TITLE_WORDS: usize = 9;
+const MAX_TRIES: usize = 20;
+
+// A word level Markov model of a book's chapter titles, so that generated
+//  chapter titles sound like the real ones. Each word is chosen by the one
+//  before it (in lower case, so "The" and "the" are the same state), with
+//  the empty string standing for the start and end of a title.
+pub struct TitleModel {
+    next_words: BTreeMap<String, Vec<String>>,
+    titles: BTreeSet<String>,
+}
+
+impl TitleModel {
+    // Learn from the titles of a book, or None if there are too few of them.
+
+    pub fn train(titles: &[String]) -> Option<TitleModel> {
+        if titles.len() < MIN_TITLES {
+            return None;
+        }
+
+        let mut model = TitleModel {
+            next_words: BTreeMap::new(),
+            titles: BTreeSet::new(),
+        };
+        for title in titles.iter() {
+            // Titles set in capitals are learned in lower case, and title
+            //  cased when generated like any other.
+            let title = if title.chars().any(|c| c.is_lowercase()) {
+                title.clone()
+            } else {
+                title.to_lowercase()
+            };
+
+            let mut previous = String::new();
+            for word in title.split_whitespace() {
+                model.add(&previous, word);
+                previous = word.to_lowercase();
+            }
+            model.add(&previous, "");
+            model.titles.insert(title_case(&title));
+        }
+
+        Some(model)
+    }
+
+    fn add(&mut self, previous: &str, word: &str) {
+        self.next_words
+            .entry(String::from(previous))
+            .or_default()
+            .push(String::from(word));
+    }
+
+    // Generate a title which isn't one of the source's, or None if every try
+    //  copies one or runs on too long.
+
+    pub fn generate(&self) -> Option<String> {
+        for _ in 0..MAX_TRIES {
+            if let Some(words) = self.generate_words() {
+                let title = title_case(&words.join(" "));
+                if !self.titles.contains(&title) {
+                    return Some(title);
+                }
+            }
+        }
+        None
+    }
+
+    // Generate the words of a title, or None if it runs on too long.
+
+    fn generate_words(&self) -> Option<Vec<String>> {
+        let mut words = Vec::new();
+        let mut previous = String::new();
+        loop {
+            let choices = &self.next_words[&previous];
+            let word = &choices[pick_random_in_range(0, choices.len() - 1)];
+            if word.is_empty() {
+                return Some(words);
+            }
+            if words.len() == MAX_TITLE_WORDS {
+                return None;
+            }
+            words.push(word.clone());
+            previous = word.to_lowercase();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use generate_text::seed_random;
+
+    fn titles(titles: &[&str]) -> Vec<String> {
+        titles.iter().map(|&title| String::from(title)).collect()
+    }
+
+    #[test]
+    fn too_few_titles_teach_nothing() {
+        assert!(TitleModel::train(&titles(&["Down the Rabbit-Hole", "The Pool of Tears"]))
+                    .is_none());
+    }
+
+    #[test]
+    fn titles_which_could_only_be_copies_are_not_generated() {
+        let model = TitleModel::train(&titles(&["One", "Two", "Three"])).unwrap();
+        assert_eq!(model.generate(), None);
+    }
+
+    #[test]
+    fn generated_titles_recombine_the_source_words() {
+        let source = titles(&["Down the Rabbit-Hole",
+                              "The Pool of Tears",
+                              "The Rabbit Sends in a Little Bill",
+                              "A MAD TEA-PARTY"]);
+        let model = TitleModel::train(&source).unwrap();
+        let words = "Down The the Rabbit-Hole Pool of Tears Rabbit Sends in a Little Bill A Mad \
+                     Tea-Party";
+
+        seed_random(1);
+        for _ in 0..20 {
+            let title = model.generate().unwrap();
+            assert!(!model.titles.contains(&title), "{}", title);
+            assert!(title.split(' ').all(|word| words.split(' ').any(|known| known == word)),
+                    "{}",
+                    title);
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use std::cmp;
+use pipeline::{Pass, Pipeline};
+use generate_text::seed_random;
+
+// How many lines ahead to look for the two texts to line up again after a
+//  difference.
+const RESYNC_WINDOW: usize = 20;
+
+// How many lines around a difference a pass sees when saying whether it may
+//  make it: a chapter's title, say, is only a heading under its label.
+const CONTEXT_LINES: usize = 2;
+
+// How many differences to show for each pass, and how many lines of each.
+const MAX_HUNKS_SHOWN: usize = 5;
+const MAX_LINES_SHOWN: usize = 10;
+
+// A run of lines which differ between a text and its round trip.
+struct Hunk<'a> {
+    line: usize,
+    new_line: usize,
+    removed: Vec<&'a str>,
+    added: Vec<&'a str>,
+}
+
+// Run a corpus through its pipeline and back, and report what didn't survive
+//  the round trip. Passes may share characters (several make apostrophes), so
+//  a loss is put down to the first pass whose addition to the pipeline
+//  changes the round trip. Passes which are lossy by design (like replacing
+//  chapter titles with a token) are reported, but only differences a pass
+//  doesn't say it may make count as failures. Returns whether there were
+//  none.
+
+pub fn verify_pipeline(corpus_name: &str, text: &str, pipeline: &Pipeline) -> bool {
+    println!("{}:", corpus_name);
+
+    let mut ok = true;
+    let mut previous = String::from(text);
+    for (i, pass) in pipeline.passes.iter().enumerate() {
+        let round_trip = round_trip(&pipeline.passes[..i + 1], text);
+
+        let old_lines = previous.lines().collect::<Vec<_>>();
+        let new_lines = round_trip.lines().collect::<Vec<_>>();
+        let hunks = diff_lines(&old_lines, &new_lines);
+
+        if hunks.is_empty() {
+            println!("  {}: ok", pass.name());
+        } else {
+            let changed = hunks.iter().fold(0, |total, hunk| total + hunk.removed.len());
+            let unexpected = hunks.iter()
+                                  .filter(|hunk| {
+                                      let (removed, added) = in_context(hunk,
+                                                                        &old_lines,
+                                                                        &new_lines);
+                                      !pass.may_change(&removed, &added)
+                                  })
+                                  .collect::<Vec<_>>();
+            let verdict = if unexpected.is_empty() {
+                String::from("expected")
+            } else {
+                ok = false;
+                format!("{} UNEXPECTED", unexpected.len())
+            };
+            println!("  {}: {} lines changed in {} places ({})",
+                     pass.name(),
+                     changed,
+                     hunks.len(),
+                     verdict);
+
+            // The unexpected differences are the ones worth seeing.
+            let shown = if unexpected.is_empty() {
+                hunks.iter().collect()
+            } else {
+                unexpected
+            };
+            for hunk in shown.iter().take(MAX_HUNKS_SHOWN) {
+                println!("    @@ line {}", hunk.line);
+                print_lines("-", &hunk.removed);
+                print_lines("+", &hunk.added);
+            }
+            if shown.len() > MAX_HUNKS_SHOWN {
+                println!("    ... and {} more", shown.len() - MAX_HUNKS_SHOWN);
+            }
+        }
+
+        previous = round_trip;
+    }
+
+    ok
+}
+
+// A difference's lines, with the lines around it on both sides.
+
+fn in_context(hunk: &Hunk, old: &[&str], new: &[&str]) -> (String, String) {
+    let old_start = hunk.line - 1;
+    let new_start = hunk.new_line - 1;
+    let old_end = old_start + hunk.removed.len();
+    let new_end = new_start + hunk.added.len();
+    let before = cmp::min(CONTEXT_LINES, cmp::min(old_start, new_start));
+    let after = cmp::min(CONTEXT_LINES, cmp::min(old.len() - old_end, new.len() - new_end));
+    (old[old_start - before..old_end + after].join("\n"),
+     new[new_start - before..new_end + after].join("\n"))
+}
+
+fn print_lines(prefix: &str, lines: &Vec<&str>) {
+    for line in lines.iter().take(MAX_LINES_SHOWN) {
+        println!("    {} {}", prefix, line);
+    }
+    if lines.len() > MAX_LINES_SHOWN {
+        println!("    {} ... ({} more lines)", prefix, lines.len() - MAX_LINES_SHOWN);
+    }
+}
+
+// Some inverses make random choices (like generating chapter titles), so the
+//  random numbers are reset for each round trip to keep them comparable.
+
+fn round_trip(passes: &[Box<dyn Pass>], text: &str) -> String {
+    seed_random(0);
+
+    let mut output = String::from(text);
+    for pass in passes.iter() {
+        output = pass.forward(&output);
+    }
+    for pass in passes.iter().rev() {
+        output = pass.inverse(&output);
+    }
+    output
+}
+
+fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Hunk<'a>> {
+    let mut hunks = Vec::new();
+    let mut i = 0;
+    let mut j = 0;
+
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            i += 1;
+            j += 1;
+            continue;
+        }
+
+        let (skip_old, skip_new) = resync(old, new, i, j);
+        hunks.push(Hunk {
+            line: i + 1,
+            new_line: j + 1,
+            removed: old[i..i + skip_old].to_vec(),
+            added: new[j..j + skip_new].to_vec(),
+        });
+        i += skip_old;
+        j += skip_new;
+    }
+
+    hunks
+}
+
+// Find the fewest lines to skip in each text for them to line up again.
+
+fn resync(old: &[&str], new: &[&str], i: usize, j: usize) -> (usize, usize) {
+    for distance in 1..(RESYNC_WINDOW * 2 + 1) {
+        for skip_old in 0..(distance + 1) {
+            let skip_new = distance - skip_old;
+            if skip_old > RESYNC_WINDOW || skip_new > RESYNC_WINDOW {
+                continue;
+            }
+
+            let a = i + skip_old;
+            let b = j + skip_new;
+            if a > old.len() || b > new.len() {
+                continue;
+            }
+            let both_ended = a == old.len() && b == new.len();
+            // Blank lines line up with each other too easily to count.
+            let lined_up = a < old.len() && b < new.len() && old[a] == new[b] &&
+                           !old[a].trim().is_empty();
+            if both_ended || lined_up {
+                return (skip_old, skip_new);
+            }
+        }
+    }
+
+    // Too different to line up again; the rest is one difference.
+    (old.len() - i, new.len() - j)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn differences_are_found_as_runs_of_lines() {
+        let old = ["one", "two", "three", "four"];
+        let new = ["one", "2", "three", "four", "five"];
+        let hunks = diff_lines(&old, &new);
+        assert_eq!(hunks.len(), 2);
+        assert_eq!((hunks[0].line, &hunks[0].removed, &hunks[0].added),
+                   (2, &vec!["two"], &vec!["2"]));
+        assert_eq!((hunks[1].line, &hunks[1].removed, &hunks[1].added),
+                   (5, &vec![], &vec!["five"]));
+        assert_eq!(in_context(&hunks[0], &old, &new),
+                   (String::from("one\ntwo\nthree\nfour"), String::from("one\n2\nthree\nfour")));
+    }
+
+    #[test]
+    fn identical_texts_have_no_differences() {
+        let lines = ["one", "", "two"];
+        assert!(diff_lines(&lines, &lines).is_empty());
+    }
+}
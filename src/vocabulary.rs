@@ -0,0 +1,453 @@
+use std::collections::{BTreeMap, BTreeSet};
+use regex::Regex;
+
+// Words that introduce or follow a speaker ("said the Hatter", "Alice
+//  replied"), which mark the name next to them as a person's.
+const SPEECH_VERBS: [&str; 16] = ["said", "says", "replied", "cried", "asked",
+                                  "answered", "exclaimed", "thought", "shouted",
+                                  "whispered", "called", "added", "continued",
+                                  "answer", "spake", "spoke"];
+
+// Words that come before people's names.
+const TITLES: [&str; 16] = ["mr", "mrs", "miss", "ms", "dr", "sir", "lady", "lord",
+                            "king", "queen", "prince", "princess", "captain", "uncle",
+                            "aunt", "son"];
+
+// Prepositions which come before places ("in Wonderland", "through Troy").
+const PLACE_PREPOSITIONS: [&str; 11] = ["in", "into", "inside", "within", "through",
+                                        "throughout", "across", "near", "towards",
+                                        "around", "beneath"];
+
+// Endings of place names.
+const PLACE_ENDINGS: [&str; 14] = ["land", "shire", "ton", "ford", "burg", "bury",
+                                   "ia", "polis", "hole", "ground", "wood", "field",
+                                   "hill", "mount"];
+
+// Common words found after "the" or "a" which make poor title nouns.
+const COMMON_WORDS: [&str; 40] = ["other", "same", "first", "last", "little", "great",
+                                  "whole", "next", "only", "most", "best", "very",
+                                  "more", "such", "least", "rest", "moment", "time",
+                                  "thing", "things", "while", "sort", "kind", "long",
+                                  "matter", "right", "left", "wrong", "good", "poor",
+                                  "young", "day", "world", "case", "part", "place",
+                                  "course", "fact", "means", "end"];
+
+// How often a word must appear to be taken into the vocabulary.
+const MIN_NAME_COUNT: usize = 2;
+const MIN_THING_COUNT: usize = 3;
+
+// How many words of each kind to keep, most frequent first.
+const MAX_PEOPLE: usize = 40;
+const MAX_PLACES: usize = 20;
+const MAX_THINGS: usize = 40;
+
+// How often a chapter must use a word for it to be what the chapter is about,
+//  and how many names and nouns it can be about.
+const MIN_SALIENT_COUNT: usize = 2;
+const MAX_SALIENT: usize = 3;
+
+// Title and author vocabulary found in a corpus: the names of its people and
+//  places, and the things it talks about.
+pub struct Vocabulary {
+    pub people: BTreeSet<String>,
+    pub places: BTreeSet<String>,
+    pub things: BTreeSet<String>,
+    // The single words of people's names, to make authors' names from.
+    pub names: BTreeSet<String>,
+}
+
+struct Word<'a> {
+    text: &'a str,
+    // Whether the word is the first of a sentence (or of a quotation), where
+    //  every word is capitalized.
+    starts_sentence: bool,
+    // Whether punctuation follows the word, as it often does nouns.
+    before_punctuation: bool,
+    possessive: bool,
+}
+
+// Scores for a capitalized name: how often it appears, and how often in ways
+//  which suggest a person or a place.
+#[derive(Default)]
+struct Candidate {
+    count: usize,
+    person: usize,
+    place: usize,
+}
+
+// Find names and nouns in a corpus for titles and authors' names. Names are
+//  capitalized words (or pairs of them, like "Mock Turtle") which appear in
+//  the middle of sentences, and more often capitalized than not. Those near
+//  speech verbs, after titles or in the possessive are taken to be people,
+//  those after prepositions of place or with place-like endings to be places,
+//  and the rest to be things. Common nouns which often follow "the" or "a"
+//  are things too.
+
+pub fn derive_vocabulary(text: &str) -> Vocabulary {
+    let words = split_words(text);
+
+    let mut lowercase_counts = BTreeMap::new();
+    for word in words.iter() {
+        if !is_capitalized(word.text) {
+            *lowercase_counts.entry(word.text.to_lowercase()).or_insert(0) += 1;
+        }
+    }
+
+    let mut candidates: BTreeMap<String, Candidate> = BTreeMap::new();
+    let mut i = 0;
+    while i < words.len() {
+        if words[i].starts_sentence || !is_name_like(words[i].text) {
+            i += 1;
+            continue;
+        }
+
+        // A second capitalized word straight after makes a two word name.
+        let length = if i + 1 < words.len() && !words[i + 1].starts_sentence &&
+                        !words[i].possessive &&
+                        is_name_like(words[i + 1].text) {
+            2
+        } else {
+            1
+        };
+        let name = words[i..i + length].iter().map(|word| word.text).collect::<Vec<_>>().join(" ");
+        let last = &words[i + length - 1];
+
+        let previous = lowercase_before(&words, i, 1);
+        let before_the = if previous.as_ref().is_some_and(|word| word == "the") {
+            lowercase_before(&words, i, 2)
+        } else {
+            previous.clone()
+        };
+        let next = words.get(i + length).map(|word| word.text.to_lowercase());
+
+        let candidate = candidates.entry(name).or_default();
+        candidate.count += 1;
+        if is_one_of(&before_the, &SPEECH_VERBS) || is_one_of(&next, &SPEECH_VERBS) {
+            candidate.person += 1;
+        }
+        if is_one_of(&previous, &TITLES) {
+            candidate.person += 1;
+        }
+        if last.possessive {
+            candidate.person += 1;
+        }
+        if is_one_of(&before_the, &PLACE_PREPOSITIONS) {
+            candidate.place += 1;
+        }
+
+        i += length;
+    }
+
+    let mut people = Vec::new();
+    let mut places = Vec::new();
+    let mut thing_counts = Vec::new();
+    for (name, candidate) in candidates.iter() {
+        // Words which are usually lower case are only capitalized for
+        //  emphasis, or in titles.
+        let lowercase_count = name.split(' ')
+                                  .map(|part| {
+                                      *lowercase_counts.get(&part.to_lowercase()).unwrap_or(&0)
+                                  })
+                                  .max()
+                                  .unwrap_or(0);
+        if candidate.count < MIN_NAME_COUNT || lowercase_count >= candidate.count {
+            continue;
+        }
+
+        let lowercase_name = name.to_lowercase();
+        let place_ending = PLACE_ENDINGS.iter().any(|ending| lowercase_name.ends_with(ending));
+        let place_score = candidate.place * 2 + if place_ending { 2 } else { 0 };
+        if candidate.person > 0 && candidate.person * 2 >= place_score {
+            people.push((candidate.count, name.clone()));
+        } else if place_score > 0 {
+            places.push((candidate.count, name.clone()));
+        } else {
+            thing_counts.push((candidate.count, name.clone()));
+        }
+    }
+
+    for (noun, count) in find_nouns(&words).into_iter() {
+        thing_counts.push((count, title_case(noun)));
+    }
+
+    people.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+    places.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+    thing_counts.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+
+    let mut vocabulary = Vocabulary {
+        people: BTreeSet::new(),
+        places: places.into_iter().take(MAX_PLACES).map(|(_, name)| name).collect(),
+        things: thing_counts.into_iter().take(MAX_THINGS).map(|(_, name)| name).collect(),
+        names: BTreeSet::new(),
+    };
+    for (_, name) in people.into_iter().take(MAX_PEOPLE) {
+        vocabulary.names.extend(name.split(' ').map(String::from));
+        vocabulary.people.insert(name);
+    }
+
+    vocabulary
+}
+
+// Common nouns: words after an article which are sometimes followed by
+//  punctuation (which adjectives rarely are), with how often they follow an
+//  article.
+
+fn find_nouns<'a>(words: &[Word<'a>]) -> Vec<(&'a str, usize)> {
+    let mut noun_counts: BTreeMap<&str, (usize, bool)> = BTreeMap::new();
+    for (i, word) in words.iter().enumerate() {
+        let after_article = match lowercase_before(words, i, 1) {
+            Some(ref previous) => previous == "the" || previous == "a" || previous == "an",
+            None => false,
+        };
+        if !after_article || word.text.chars().count() < 4 || is_capitalized(word.text) ||
+           word.possessive || COMMON_WORDS.contains(&word.text) {
+            continue;
+        }
+        let entry = noun_counts.entry(word.text).or_insert((0, false));
+        entry.0 += 1;
+        entry.1 = entry.1 || word.before_punctuation;
+    }
+    noun_counts.into_iter()
+               .filter(|&(_, (count, before_punctuation))| {
+                   count >= MIN_THING_COUNT && before_punctuation
+               })
+               .map(|(noun, (count, _))| (noun, count))
+               .collect()
+}
+
+// How often each word appears in a corpus, and which of its words are names
+//  and nouns, to tell what a chapter generated from it is about.
+pub struct CorpusWords {
+    // Counts of words in lower case.
+    counts: BTreeMap<String, usize>,
+    total: usize,
+    names: BTreeSet<String>,
+    nouns: BTreeSet<String>,
+}
+
+// The words a chapter is most about: names and nouns which it uses often,
+//  and more often than the corpus as a whole does, most salient first.
+pub struct Salient {
+    pub names: Vec<String>,
+    pub nouns: Vec<String>,
+}
+
+impl Salient {
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty() && self.nouns.is_empty()
+    }
+}
+
+impl CorpusWords {
+    pub fn new() -> CorpusWords {
+        CorpusWords {
+            counts: BTreeMap::new(),
+            total: 0,
+            names: BTreeSet::new(),
+            nouns: BTreeSet::new(),
+        }
+    }
+
+    // Count the words of a corpus. Names are single words capitalized in the
+    //  middle of sentences more often than they appear in lower case.
+
+    pub fn count(text: &str) -> CorpusWords {
+        let words = split_words(text);
+
+        let mut corpus_words = CorpusWords::new();
+        let mut capitalized_counts = BTreeMap::new();
+        for word in words.iter() {
+            let lowercase = word.text.to_lowercase();
+            if !word.starts_sentence && is_name_like(word.text) {
+                *capitalized_counts.entry(lowercase.clone()).or_insert(0) += 1;
+            }
+            *corpus_words.counts.entry(lowercase).or_insert(0) += 1;
+            corpus_words.total += 1;
+        }
+        for (name, &capitalized_count) in capitalized_counts.iter() {
+            let count = corpus_words.counts[name];
+            if capitalized_count >= MIN_NAME_COUNT && capitalized_count * 2 > count {
+                corpus_words.names.insert(name.clone());
+            }
+        }
+        for (noun, _) in find_nouns(&words).into_iter() {
+            corpus_words.nouns.insert(noun.to_lowercase());
+        }
+
+        corpus_words
+    }
+
+    // The names found in the corpus, in lower case.
+
+    pub fn names(&self) -> &BTreeSet<String> {
+        &self.names
+    }
+
+    // Find what a chapter is about. Each name and noun it uses at least
+    //  twice is scored by how often it does, weighted by how much more often
+    //  than in the corpus as a whole (the log of the ratio), so that a
+    //  chapter's own characters outweigh the book's hero, who is in every
+    //  chapter.
+
+    pub fn salient(&self, chapter: &str) -> Salient {
+        let mut chapter_counts: BTreeMap<String, (usize, &str)> = BTreeMap::new();
+        let words = split_words(chapter);
+        if words.is_empty() || self.total == 0 {
+            return Salient {
+                names: Vec::new(),
+                nouns: Vec::new(),
+            };
+        }
+        for word in words.iter() {
+            let entry = chapter_counts.entry(word.text.to_lowercase()).or_insert((0, word.text));
+            entry.0 += 1;
+            // Names are kept as they're capitalized.
+            if is_capitalized(word.text) && !word.starts_sentence {
+                entry.1 = word.text;
+            }
+        }
+
+        let mut names = Vec::new();
+        let mut nouns = Vec::new();
+        for (word, &(count, text)) in chapter_counts.iter() {
+            let corpus_count = match self.counts.get(word) {
+                Some(&corpus_count) if count >= MIN_SALIENT_COUNT => corpus_count,
+                // Words the corpus doesn't have are the generator's inventions.
+                _ => continue,
+            };
+            let chapter_rate = count as f64 / words.len() as f64;
+            let corpus_rate = corpus_count as f64 / self.total as f64;
+            let score = count as f64 * (chapter_rate / corpus_rate).ln();
+            if score <= 0.0 {
+                continue;
+            }
+            if self.names.contains(word) && is_name_like(text) {
+                names.push((score, String::from(text)));
+            } else if self.nouns.contains(word) {
+                nouns.push((score, title_case(word)));
+            }
+        }
+
+        Salient {
+            names: most_salient(names),
+            nouns: most_salient(nouns),
+        }
+    }
+}
+
+fn most_salient(mut scored: Vec<(f64, String)>) -> Vec<String> {
+    scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap().then_with(|| a.1.cmp(&b.1)));
+    scored.into_iter().take(MAX_SALIENT).map(|(_, word)| word).collect()
+}
+
+fn split_words<'a>(text: &'a str) -> Vec<Word<'a>> {
+    let word_pattern = Regex::new(r"[\p{L}\p{N}]+(?:['’ʼ-][\p{L}]+)*").unwrap();
+
+    let mut words: Vec<Word> = Vec::new();
+    let mut previous_end = 0;
+    for (start, end) in word_pattern.find_iter(text) {
+        let gap = &text[previous_end..start];
+        // Sentences start after full stops and the like, and quotations
+        //  after opening quotes (straight single quotes only count at the
+        //  start of the gap, as they are apostrophes elsewhere). Lines are
+        //  counted too, as every line of verse is capitalized.
+        let starts_sentence = previous_end == 0 || gap.contains('\n') ||
+                              gap.chars().any(|c| ".!?:\"“‘(".contains(c)) ||
+                              gap.ends_with(" '") || gap.ends_with("\n'");
+
+        let mut word = &text[start..end];
+        let mut possessive = false;
+        for suffix in ["'s", "’s", "ʼs"].iter() {
+            if word.ends_with(suffix) && word.len() > suffix.len() {
+                word = &word[..word.len() - suffix.len()];
+                possessive = true;
+            }
+        }
+
+        if let Some(previous) = words.last_mut() {
+            previous.before_punctuation = gap.chars().any(|c| ".,;:!?".contains(c));
+        }
+        words.push(Word {
+            text: word,
+            starts_sentence,
+            before_punctuation: false,
+            possessive,
+        });
+        previous_end = end;
+    }
+
+    words
+}
+
+// The word the given distance before the i-th, in lower case, unless a
+//  sentence starts in between.
+
+fn lowercase_before(words: &[Word], i: usize, distance: usize) -> Option<String> {
+    if i < distance || (0..distance).any(|back| words[i - back].starts_sentence) {
+        return None;
+    }
+    Some(words[i - distance].text.to_lowercase())
+}
+
+fn is_one_of(word: &Option<String>, list: &[&str]) -> bool {
+    word.as_ref().is_some_and(|word| list.contains(&&word[..]))
+}
+
+fn is_capitalized(word: &str) -> bool {
+    word.chars().next().is_some_and(|c| c.is_uppercase())
+}
+
+// Capitalized words that could be names: not a single letter (like "I"),
+//  not all in capitals (like a heading) and not a contraction (like "I'm").
+
+fn is_name_like(word: &str) -> bool {
+    let mut letters = word.chars().filter(|c| c.is_alphabetic());
+    is_capitalized(word) && word.chars().count() > 1 && letters.any(|c| c.is_lowercase()) &&
+        !word.contains(['\'', '’', 'ʼ'])
+}
+
+fn title_case(word: &str) -> String {
+    word.split('-')
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "Once there was a girl. \"Look at the rabbit!\" said Alice. She ran \
+                        after the rabbit, and Alice saw the Queen's garden. \"Run,\" said \
+                        Alice. They went into Wonderland, and then back to Wonderland. \
+                        Then the rabbit ran into the garden. The roses in the garden grew, and \
+                        Alice sat in the garden.";
+
+    #[test]
+    fn people_places_and_things_are_found() {
+        let vocabulary = derive_vocabulary(TEXT);
+        assert!(vocabulary.people.contains("Alice"));
+        assert!(vocabulary.names.contains("Alice"));
+        assert!(vocabulary.places.contains("Wonderland"));
+        assert!(vocabulary.things.contains("Rabbit"));
+        assert!(vocabulary.things.contains("Garden"));
+        // Words capitalized only at the start of a sentence are no names.
+        assert!(!vocabulary.people.contains("Then") && !vocabulary.things.contains("Then"));
+    }
+
+    #[test]
+    fn chapters_are_about_what_they_use_more_than_the_corpus() {
+        let corpus_words = CorpusWords::count(TEXT);
+        let salient = corpus_words.salient("The rabbit ran, and the rabbit hid from Alice. The \
+                                            rabbit was late.");
+        assert_eq!(salient.nouns, vec!["Rabbit"]);
+        assert!(salient.names.is_empty());
+        assert!(corpus_words.salient("Nothing here is known.").is_empty());
+    }
+}
@@ -0,0 +1,691 @@
+use headings::{HeadingKind, HeadingRenderer, RenderedHeading};
+use pipeline::Pipeline;
+use structure::Structure;
+use sentence::SentenceWatcher;
+use title_generator::TitlePieces;
+use matter::Matter;
+use reflow::reflow;
+
+// Plain text epigraphs of prose are wrapped to this width, before they're
+//  indented.
+const EPIGRAPH_WIDTH: usize = 60;
+
+#[derive(Debug,Eq,PartialEq,Copy,Clone)]
+pub enum Format {
+    Text,
+    Html,
+    Latex,
+    Markdown,
+}
+
+pub const FORMATS: [&str; 4] = ["text", "html", "latex", "markdown"];
+
+impl Format {
+    pub fn parse(name: &str) -> Option<Format> {
+        match name {
+            "text" => Some(Format::Text),
+            "html" => Some(Format::Html),
+            "latex" => Some(Format::Latex),
+            "markdown" => Some(Format::Markdown),
+            _ => None,
+        }
+    }
+}
+
+// Writes generated text in an output format, rendering the structure tokens
+//  the generator emits (headings, breaks, emphasis) the way the format marks
+//  them up.
+//
+// Generated text is still preprocessed (curly quotes, em dashes, modifier
+//  apostrophes). Plain text is turned back into the corpus's own conventions
+//  by the pipeline's inverses; the other formats set the characters the way
+//  the format does typography instead.
+pub struct Writer<'a> {
+    format: Format,
+    pipeline: &'a Pipeline,
+    // Plain text generated since the last piece of structure, waiting for
+    //  the inverses to run over it.
+    text_run: String,
+    headings: HeadingRenderer<'a>,
+    // A heading with a title is only rendered once its chapter is written,
+    //  so that the title can be about the chapter. Until then this holds its
+    //  kind and where in the output it goes.
+    pending_heading: Option<(HeadingKind, usize)>,
+    chapter: String,
+    min_order: usize,
+    max_order: usize,
+    form_max_order: usize,
+    emphasis_open: bool,
+    // Whether nothing but line breaks has been generated since the last
+    //  heading or break, and whether the last character was a line break.
+    block_start: bool,
+    line_start: bool,
+    // Html marks every other sentence, so they can be told apart.
+    sentences: SentenceWatcher,
+    odd_sentence: bool,
+    output: String,
+}
+
+impl<'a> Writer<'a> {
+    pub fn new(format: Format,
+               pipeline: &'a Pipeline,
+               titles: &'a TitlePieces,
+               min_order: usize,
+               max_order: usize,
+               form_max_order: usize)
+               -> Writer<'a> {
+        Writer {
+            format,
+            pipeline,
+            text_run: String::new(),
+            headings: HeadingRenderer::new(&pipeline.headings, titles),
+            pending_heading: None,
+            chapter: String::new(),
+            min_order,
+            max_order,
+            form_max_order,
+            emphasis_open: false,
+            block_start: true,
+            line_start: false,
+            sentences: SentenceWatcher::new(),
+            odd_sentence: false,
+            output: String::new(),
+        }
+    }
+
+    // Write whatever the format needs before the text: a header, and the
+    //  title and author.
+
+    pub fn begin(&mut self, title: &str, author: &str) {
+        match self.format {
+            Format::Text => {
+                self.output.push_str(title);
+                self.output.push_str("\n\nby ");
+                self.output.push_str(author);
+                self.output.push_str(" \n\n");
+            }
+            Format::Html => {
+                write_html_header(&mut self.output,
+                                  self.min_order,
+                                  self.max_order,
+                                  self.form_max_order);
+                self.output.push_str("<h1>");
+                self.output.push_str(&escape_html(title));
+                self.output.push_str("</h1>by ");
+                self.output.push_str(&escape_html(author));
+                self.output.push_str("\n\n");
+            }
+            Format::Latex => {
+                self.output.push_str("\\documentclass[openany]{memoir}\n\n");
+                self.output.push_str("\\title{");
+                self.output.push_str(&escape_latex(title));
+                self.output.push_str("}\n\\author{");
+                self.output.push_str(&escape_latex(author));
+                self.output.push_str("}\n\\date{}\n\\begin{document}\n\n\\maketitle\n\n");
+            }
+            Format::Markdown => {
+                self.output.push_str("# ");
+                self.output.push_str(&escape_markdown(title));
+                self.output.push_str("\n\nby ");
+                self.output.push_str(&escape_markdown(author));
+                self.output.push_str("\n\n");
+            }
+        }
+    }
+
+    // Write the dedication and the epigraph, whichever there are, between
+    //  the title and the text.
+
+    pub fn front_matter(&mut self, matter: &Matter) {
+        if let Some(ref dedication) = matter.dedication {
+            self.output.push_str(&match self.format {
+                Format::Text => format!("{}\n\n", dedication),
+                Format::Html => {
+                    format!("<p class=\"dedication\"><i>{}</i></p>\n\n",
+                            escape_html(dedication))
+                }
+                Format::Latex => {
+                    format!("\\begin{{center}}\n\\emph{{{}}}\n\\end{{center}}\n\n",
+                            escape_latex(dedication))
+                }
+                Format::Markdown => format!("*{}*\n\n", escape_markdown(dedication)),
+            });
+        }
+        if let Some(ref epigraph) = matter.epigraph {
+            self.output.push_str(&match self.format {
+                Format::Text => {
+                    // Prose is wrapped here, as the indented epigraph would
+                    //  pass for verse when the text is reflowed.
+                    let text = reflow(&self.typeset(&epigraph.text), EPIGRAPH_WIDTH);
+                    let lines = text.lines().map(|line| format!("    {}\n", line));
+                    format!("{}        \u{2014} {}\n\n",
+                            lines.collect::<String>(),
+                            epigraph.attribution)
+                }
+                Format::Html => {
+                    format!("<blockquote class=\"epigraph\">{}<br>\u{2014} {}</blockquote>\n\n",
+                            self.typeset(&epigraph.text),
+                            escape_html(&epigraph.attribution))
+                }
+                Format::Latex => {
+                    format!("\\epigraph{{{}}}{{{}}}\n\n",
+                            self.typeset(&epigraph.text),
+                            escape_latex(&epigraph.attribution))
+                }
+                Format::Markdown => {
+                    format!("> {}\n>\n> \u{2014} {}\n\n",
+                            self.typeset(&epigraph.text),
+                            escape_markdown(&epigraph.attribution))
+                }
+            });
+        }
+    }
+
+    // Write the blurb and the colophon after the text.
+
+    fn back_matter(&mut self, matter: &Matter) {
+        if matter.blurb.is_none() && matter.colophon.is_empty() {
+            return;
+        }
+        self.output.push_str(match self.format {
+            Format::Text => "\n\n* * *\n\n",
+            Format::Html => "\n\n<hr>\n\n",
+            Format::Latex => "\n\n\\clearpage\n\n",
+            Format::Markdown => "\n\n---\n\n",
+        });
+        if let Some(ref blurb) = matter.blurb {
+            self.output.push_str(&match self.format {
+                Format::Text => format!("{}\n\n", blurb),
+                Format::Html => format!("<div class=\"blurb\">{}</div>\n\n", escape_html(blurb)),
+                Format::Latex => {
+                    format!("\\begin{{quote}}\n{}\n\\end{{quote}}\n\n", escape_latex(blurb))
+                }
+                Format::Markdown => format!("{}\n\n", escape_markdown(blurb)),
+            });
+        }
+        if !matter.colophon.is_empty() {
+            self.output.push_str(&match self.format {
+                Format::Text => matter.colophon.join("\n"),
+                Format::Html => {
+                    let lines = matter.colophon
+                                      .iter()
+                                      .map(|line| escape_html(line))
+                                      .collect::<Vec<_>>();
+                    format!("<div class=\"colophon\"><small>{}</small></div>", lines.join("<br>"))
+                }
+                Format::Latex => {
+                    let lines = matter.colophon
+                                      .iter()
+                                      .map(|line| escape_latex(line))
+                                      .collect::<Vec<_>>();
+                    format!("{{\\small\\noindent {}\\par}}", lines.join("\\\\\n"))
+                }
+                Format::Markdown => {
+                    let lines = matter.colophon
+                                      .iter()
+                                      .map(|line| escape_markdown(line))
+                                      .collect::<Vec<_>>();
+                    format!("<small>{}</small>", lines.join("  \n"))
+                }
+            });
+        }
+    }
+
+    // Write a generated character, along with the text order and form order
+    //  it was generated with (which html shows as color coding).
+
+    pub fn text(&mut self, c: char, order: usize, form_order: usize) {
+        if self.sentences.push(c) {
+            self.odd_sentence = !self.odd_sentence;
+        }
+        self.chapter.push(c);
+
+        // A line break at the start of a block, or straight after another,
+        //  would only leave a blank line (and in latex, a line break with no
+        //  line to end).
+        if c == '\n' && (self.block_start || self.line_start) {
+            return;
+        }
+        self.block_start = false;
+        self.line_start = c == '\n';
+
+        match self.format {
+            Format::Text | Format::Markdown => self.text_run.push(c),
+            Format::Html => {
+                self.output.push_str("<span class=\"");
+                self.output.push_str("order-");
+                self.output.push_str(&order.to_string());
+                self.output.push_str(" form-order-");
+                self.output.push_str(&form_order.to_string());
+                if self.odd_sentence {
+                    self.output.push_str(" sentence-odd");
+                }
+                self.output.push_str("\">");
+                let text = self.typeset(&c.to_string());
+                self.output.push_str(&text);
+                self.output.push_str("</span>");
+            }
+            // A line break within a paragraph (in verse, say) has to be
+            //  explicit.
+            Format::Latex if c == '\n' => self.output.push_str("\\\\\n"),
+            Format::Latex => {
+                let text = self.typeset(&c.to_string());
+                self.output.push_str(&text);
+            }
+        }
+    }
+
+    // Turn generated text into the format's own: plain text through the
+    //  pipeline's inverses, the rest escaped, with the format's typography.
+
+    fn typeset(&self, text: &str) -> String {
+        match self.format {
+            Format::Text => self.pipeline.inverse(text),
+            Format::Html => escape_html(&typeset_unicode(text)),
+            Format::Latex => typeset_latex(text),
+            Format::Markdown => escape_markdown(&typeset_unicode(text)),
+        }
+    }
+
+    // Write out the plain text generated so far, now that the inverses can
+    //  see it whole (a dash pass, say, turns one character into two), or
+    //  markdown can see where its lines start.
+
+    fn flush_text(&mut self) {
+        if !self.text_run.is_empty() {
+            let text = self.typeset(&self.text_run);
+            self.output.push_str(&text);
+            self.text_run.clear();
+        }
+    }
+
+    pub fn structure(&mut self, structure: Structure) {
+        self.flush_text();
+        self.sentences.push(structure.token());
+
+        match structure {
+            Structure::Heading(kind) => {
+                self.close_emphasis();
+                self.end_block();
+                if self.headings.has_title(kind) {
+                    self.end_chapter();
+                    self.pending_heading = Some((kind, self.output.len()));
+                } else if let Some(heading) = self.headings.render(kind) {
+                    let text = self.format_heading(kind, &heading);
+                    self.output.push_str(&text);
+                }
+                self.block_start = true;
+            }
+            Structure::SceneBreak => {
+                self.close_emphasis();
+                self.output.push_str(match self.format {
+                    Format::Text | Format::Markdown => "* * *",
+                    Format::Html => "<hr>",
+                    Format::Latex => "\\begin{center}* * *\\end{center}",
+                });
+            }
+            Structure::Paragraph | Structure::Stanza => {
+                self.close_emphasis();
+                self.output.push_str("\n\n");
+                self.chapter.push_str("\n\n");
+                self.block_start = true;
+                self.line_start = false;
+            }
+            Structure::EmphasisStart => {
+                if !self.emphasis_open {
+                    self.output.push_str(match self.format {
+                        Format::Text => "_",
+                        Format::Html => "<em>",
+                        Format::Latex => "\\emph{",
+                        Format::Markdown => "*",
+                    });
+                    self.emphasis_open = true;
+                }
+            }
+            Structure::EmphasisEnd => self.close_emphasis(),
+        }
+    }
+
+    // End the paragraph before a heading, with the blank line the format
+    //  separates blocks by, if the generator went straight from a line of
+    //  text to the heading.
+
+    fn end_block(&mut self) {
+        if self.block_start {
+            return;
+        }
+        if !self.line_start {
+            self.output.push_str("\n\n");
+        } else if self.format == Format::Latex {
+            let length = self.output.len() - "\\\\\n".len();
+            self.output.truncate(length);
+            self.output.push_str("\n\n");
+        } else {
+            self.output.push('\n');
+        }
+        self.block_start = true;
+        self.line_start = false;
+    }
+
+    // Render the heading of the chapter just written, now that its title can
+    //  be about it, and start the next chapter.
+
+    fn end_chapter(&mut self) {
+        if let Some((kind, offset)) = self.pending_heading.take() {
+            if let Some(heading) = self.headings.render_about(kind, &self.chapter) {
+                let text = self.format_heading(kind, &heading);
+                self.output.insert_str(offset, &text);
+            }
+        }
+        self.chapter.clear();
+    }
+
+    fn format_heading(&self, kind: HeadingKind, heading: &RenderedHeading) -> String {
+        match self.format {
+            Format::Text => heading.to_text(),
+            _ => format_heading(self.format, kind, &heading.to_line()),
+        }
+    }
+
+    // Emphasis is only closed if it's open, and is closed at the end of a
+    //  paragraph at the latest, so that generated emphasis always balances.
+
+    fn close_emphasis(&mut self) {
+        if self.emphasis_open {
+            self.output.push_str(match self.format {
+                Format::Text => "_",
+                Format::Html => "</em>",
+                Format::Latex => "}",
+                Format::Markdown => "*",
+            });
+            self.emphasis_open = false;
+            self.line_start = false;
+        }
+    }
+
+    // Close the text and write the back matter after it.
+
+    pub fn finish(mut self, matter: &Matter) -> String {
+        self.flush_text();
+        self.close_emphasis();
+        self.end_chapter();
+
+        let trimmed_length = self.output.trim_end().len();
+        self.output.truncate(trimmed_length);
+        self.back_matter(matter);
+
+        if self.format == Format::Latex {
+            self.output.push_str("\n\n\\end{document}\n");
+        }
+
+        self.output
+    }
+}
+
+fn format_heading(format: Format, kind: HeadingKind, line: &str) -> String {
+    let line = line.trim();
+    match (format, kind) {
+        (Format::Html, HeadingKind::Aphorism) => format!("<b>{}</b> ", escape_html(line)),
+        (Format::Html, HeadingKind::Section) => format!("<h3>{}</h3>", escape_html(line)),
+        (Format::Html, _) => format!("<h2>{}</h2>", escape_html(line)),
+        (Format::Latex, HeadingKind::Aphorism) => format!("\\textbf{{{}}} ", escape_latex(line)),
+        (Format::Latex, HeadingKind::Part) => format!("\\part*{{{}}}", escape_latex(line)),
+        (Format::Latex, HeadingKind::Chapter) => format!("\\chapter*{{{}}}", escape_latex(line)),
+        (Format::Latex, HeadingKind::Section) => format!("\\section*{{{}}}", escape_latex(line)),
+        (Format::Markdown, HeadingKind::Aphorism) => format!("**{}** ", escape_markdown(line)),
+        (Format::Markdown, HeadingKind::Section) => format!("### {}", escape_markdown(line)),
+        (_, _) => format!("## {}", escape_markdown(line)),
+    }
+}
+
+// Html and markdown can show the curly quotes and dashes as they are; only
+//  the modifier letter apostrophe is set as the usual right single quote.
+
+fn typeset_unicode(text: &str) -> String {
+    text.replace('\u{02BC}', "\u{2019}")
+}
+
+// Latex has its own ligatures for quotes and dashes, which typeset better
+//  than the Unicode characters do.
+
+fn typeset_latex(text: &str) -> String {
+    let mut output = String::new();
+    for c in text.chars() {
+        match c {
+            '\u{201C}' => output.push_str("``"),
+            '\u{201D}' => output.push_str("''"),
+            '\u{2018}' => output.push('`'),
+            '\u{2019}' | '\u{02BC}' => output.push('\''),
+            '\u{2014}' => output.push_str("---"),
+            '\u{2013}' => output.push_str("--"),
+            _ => output.push_str(&escape_latex(&c.to_string())),
+        }
+    }
+    output
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+fn escape_latex(text: &str) -> String {
+    let mut output = String::new();
+    for c in text.chars() {
+        match c {
+            '\\' => output.push_str("\\textbackslash{}"),
+            '~' => output.push_str("\\textasciitilde{}"),
+            '^' => output.push_str("\\textasciicircum{}"),
+            '&' | '%' | '$' | '#' | '_' | '{' | '}' => {
+                output.push('\\');
+                output.push(c);
+            }
+            _ => output.push(c),
+        }
+    }
+    output
+}
+
+// Markdown takes some characters as markup anywhere, and others only at the
+//  start of a line, where they begin a heading, a quote or a list. Text is
+//  taken to start a line; escaping a character that didn't need it is
+//  harmless.
+
+fn escape_markdown(text: &str) -> String {
+    let mut output = String::new();
+    let mut line_start = true;
+    let mut numbered = false;
+    for c in text.chars() {
+        let escape = match c {
+            '\\' | '*' | '_' | '`' | '<' | '[' => true,
+            '#' | '>' | '-' | '+' => line_start,
+            '.' | ')' => numbered,
+            _ => false,
+        };
+        if escape {
+            output.push('\\');
+        }
+        output.push(c);
+        numbered = c.is_ascii_digit() && (line_start || numbered);
+        line_start = c == '\n' || (line_start && c == ' ');
+    }
+    output
+}
+
+fn write_html_header(output_buffer: &mut String,
+                     min_order: usize,
+                     max_order: usize,
+                     form_max_order: usize) {
+    output_buffer.push_str("<meta charset=\"UTF-8\">");
+    output_buffer.push_str("<script type='text/javascript'>
+		window.onload = function () {
+			var a = document.getElementById('a');
+			var s = document.getElementById('s');
+			var update = function () {
+				document.body.className = (a.checked ? 'form-order' : 'order') +
+					(s.checked ? ' sentences' : '');
+			};
+			a.onclick = update;
+			s.onclick = update;
+			update();
+		};
+	</script>");
+    output_buffer.push_str("<style type=\"text/css\"> body { white-space: pre-wrap; } ");
+    output_buffer.push_str(".structure-success { background: #ddffdd; } ");
+    output_buffer.push_str("body.sentences .sentence-odd { background: #e8eeff; } ");
+    for i in min_order..max_order + 1 {
+
+        let a = max_order + 1 - min_order;
+        let b = i - min_order;
+        let c = a - b - 1;
+        let multiplier = c as f64 / a as f64;
+
+        let value = (multiplier * 248.0) as i32;
+        output_buffer.push_str("body.order .order-");
+        output_buffer.push_str(&i.to_string());
+        output_buffer.push_str("{ ");
+
+        output_buffer.push_str(" color: rgb(");
+        output_buffer.push_str(&value.to_string());
+        output_buffer.push(',');
+        output_buffer.push_str(&value.to_string());
+        output_buffer.push(',');
+        output_buffer.push_str(&value.to_string());
+        output_buffer.push_str(");\n");
+
+        output_buffer.push_str("}\n");
+    }
+    for i in 0..form_max_order + 1 {
+
+        let a = form_max_order + 1;
+        let b = i;
+        let c = a - b - 1;
+        let multiplier = c as f64 / a as f64;
+
+        let value = (multiplier * 248.0) as i32;
+        output_buffer.push_str("body.form-order .form-order-");
+        output_buffer.push_str(&i.to_string());
+        output_buffer.push_str("{ ");
+
+        output_buffer.push_str(" color: rgb(");
+        output_buffer.push_str(&value.to_string());
+        output_buffer.push(',');
+        output_buffer.push_str(&value.to_string());
+        output_buffer.push(',');
+        output_buffer.push_str(&value.to_string());
+        output_buffer.push_str(");\n");
+
+        output_buffer.push_str("}\n");
+    }
+    output_buffer.push_str("</style>");
+    output_buffer.push_str("<input type='checkbox' id='a'> Show Form Stats <br/>");
+    output_buffer.push_str("<input type='checkbox' id='s'> Show Sentences <br/>");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use headings::HeadingPreset;
+    use matter::Epigraph;
+
+    fn no_matter() -> Matter {
+        Matter {
+            dedication: None,
+            epigraph: None,
+            blurb: None,
+            colophon: Vec::new(),
+        }
+    }
+
+    // Write preprocessed text, structure tokens and all.
+
+    fn write_tokens(format: Format, pipeline: &Pipeline, tokens: &str) -> String {
+        let titles = TitlePieces::untitled();
+        let mut writer = Writer::new(format, pipeline, &titles, 1, 4, 4);
+        for c in tokens.chars() {
+            match Structure::from_token(c) {
+                Some(structure) => writer.structure(structure),
+                None => writer.text(c, 1, 1),
+            }
+        }
+        writer.finish(&no_matter())
+    }
+
+    fn write(format: Format, source: &str) -> String {
+        let pipeline = Pipeline::standard(HeadingPreset::none()).unwrap();
+        write_tokens(format, &pipeline, &pipeline.forward(source))
+    }
+
+    #[test]
+    fn text_gets_the_corpus_conventions_back() {
+        let source = "\"Don't--\" she said & _left_.\n\nThe end.";
+        assert_eq!(write(Format::Text, source), source);
+    }
+
+    #[test]
+    fn formats_do_their_own_typography() {
+        let source = "\"Don't--\" she said & _left_.";
+        let latex = write(Format::Latex, source);
+        assert!(latex.contains("``Don't---'' she said \\& \\emph{left}."));
+
+        let markdown = write(Format::Markdown, source);
+        assert!(markdown.contains("\u{201C}Don\u{2019}t\u{2014}\u{201D} she said & *left*."));
+
+        let html = write(Format::Html, source);
+        assert!(html.contains(">\u{201C}<") && html.contains(">\u{2014}<"));
+        assert!(html.contains(">&amp;<") && !html.contains("&amp;amp;"));
+    }
+
+    #[test]
+    fn headings_start_a_block_of_their_own() {
+        let preset = HeadingPreset::parse("section roman\n    pattern (?m)^[IVX]+\\.$\n    \
+                                           suffix .\n")
+                         .unwrap();
+        let pipeline = Pipeline::standard(preset).unwrap();
+        let tokens = format!("Iron bells.\n{}{}Hear",
+                             Structure::Heading(HeadingKind::Section).token(),
+                             Structure::Paragraph.token());
+
+        assert_eq!(write_tokens(Format::Text, &pipeline, &tokens),
+                   "Iron bells.\n\nI.\n\nHear");
+        assert!(write_tokens(Format::Latex, &pipeline, &tokens)
+                    .contains("Iron bells.\n\n\\section*{I.}\n\nHear"));
+        assert!(write_tokens(Format::Markdown, &pipeline, &tokens)
+                    .starts_with("Iron bells.\n\n### I.\n\nHear"));
+    }
+
+    #[test]
+    fn line_breaks_never_start_a_paragraph() {
+        let pipeline = Pipeline::standard(HeadingPreset::none()).unwrap();
+        let tokens = format!("One,\n\ntwo.{}\nThree", Structure::Paragraph.token());
+        assert!(write_tokens(Format::Latex, &pipeline, &tokens)
+                    .contains("One,\\\\\ntwo.\n\nThree"));
+    }
+
+    #[test]
+    fn markdown_escapes_what_would_be_markup() {
+        assert_eq!(escape_markdown("# <b> [x]\n- a-b 3.5\n  12) > +"),
+                   "\\# \\<b> \\[x]\n\\- a-b 3.5\n  12\\) > +");
+    }
+
+    #[test]
+    fn text_epigraphs_are_indented_and_wrapped() {
+        let pipeline = Pipeline::standard(HeadingPreset::none()).unwrap();
+        let titles = TitlePieces::untitled();
+        let mut writer = Writer::new(Format::Text, &pipeline, &titles, 1, 4, 4);
+        let mut matter = no_matter();
+        matter.epigraph = Some(Epigraph {
+            text: String::from("Sing, O goddess, the anger of Achilles son of Peleus, that \
+                                brought countless ills upon the Achaeans."),
+            attribution: String::from("Homer"),
+        });
+        writer.front_matter(&matter);
+        let output = writer.finish(&no_matter());
+
+        let lines = output.lines().collect::<Vec<_>>();
+        assert!(lines.len() > 2);
+        assert!(lines.iter().all(|line| line.starts_with("    ") && line.chars().count() <= 64),
+                "{}",
+                output);
+        assert_eq!(lines.last(), Some(&"        \u{2014} Homer"));
+    }
+}